@@ -0,0 +1,44 @@
+//! Measures how long it takes to load grammars of increasing size.
+//!
+//! Run with `cargo bench --bench load_grammar`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Builds a grammar with `rules` rules, each with a few alternatives, comments and continued lines.
+fn grammar(rules: usize) -> String {
+    let mut source = String::from("@ignore_between: r'[ \\t\\n]+'\nfile: rule_0+ ENDMARKER\n");
+    for i in 0..rules {
+        let next = (i + 1) % rules;
+        source.push_str(&format!(
+            "# Rule number {i}\nrule_{i}: 'kw{i}' ~'(' rule_{next}?, ',' * ~')'\n    | r'[a-z]+{i}' name= NAME_{i}\n    | (rule_{next} | 'x'){{1,3}}\nNAME_{i}: 'name{i}'\n"
+        ));
+    }
+    source
+}
+
+/// Returns the fastest of a few runs of loading the grammar.
+fn time_load(source: &str) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(gramspec::load_grammar(black_box(source)).unwrap());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    for rules in [100, 200, 400, 800, 1600] {
+        let source = grammar(rules);
+        let elapsed = time_load(&source);
+        println!(
+            "{:>5} rules, {:>7} bytes: {:>10.2?} ({:.2?} per rule)",
+            rules,
+            source.len(),
+            elapsed,
+            elapsed / rules as u32
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Helpers for generating parsers from a `build.rs` script.
+//!
+//! ```no_run
+//! // In the build script's main function
+//! gramspec::build::compile("grammar.grm")
+//!     .parser_name("MyParser")
+//!     .out_file("my_parser.rs")
+//!     .run_or_exit();
+//! ```
+//!
+//! The generated file can then be included with
+//! `include!(concat!(env!("OUT_DIR"), "/my_parser.rs"));`.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::generator::templates::Templates;
+
+/// Starts configuring the generation of a parser for the given grammar file.
+pub fn compile<P: AsRef<Path>>(grammar: P) -> Builder {
+    Builder {
+        grammar: grammar.as_ref().to_path_buf(),
+        parser_name: String::from(crate::DEFAULT_PARSER_NAME),
+        indent: String::from(crate::DEFAULT_INDENT),
+        template_dir: None,
+        out_dir: None,
+        out_file: None,
+    }
+}
+
+/// Configures how a grammar is compiled into a parser source file.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    grammar: PathBuf,
+    parser_name: String,
+    indent: String,
+    template_dir: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    out_file: Option<PathBuf>,
+}
+
+impl Builder {
+    /// Sets the name of the generated parser struct.
+    pub fn parser_name(mut self, parser_name: &str) -> Self {
+        self.parser_name = parser_name.to_string();
+        self
+    }
+
+    /// Sets the indentation used in the generated code.
+    pub fn indent(mut self, indent: &str) -> Self {
+        self.indent = indent.to_string();
+        self
+    }
+
+    /// Sets a directory whose template files replace the default templates.
+    pub fn template_dir<P: AsRef<Path>>(mut self, template_dir: P) -> Self {
+        self.template_dir = Some(template_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the directory the parser is written to. Defaults to `OUT_DIR`.
+    pub fn out_dir<P: AsRef<Path>>(mut self, out_dir: P) -> Self {
+        self.out_dir = Some(out_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the file name of the generated parser, relative to the output directory.
+    /// Defaults to the grammar file name with an `.rs` extension.
+    pub fn out_file<P: AsRef<Path>>(mut self, out_file: P) -> Self {
+        self.out_file = Some(out_file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generates the parser and returns the path it was written to.
+    ///
+    /// Prints `cargo:rerun-if-changed` lines for the grammar, the files it includes and any
+    /// template overrides, so cargo only reruns the build script when one of them changes.
+    pub fn run(self) -> Result<PathBuf, Error> {
+        // The files read so far are watched even if generation fails, so that fixing them reruns it
+        let mut watched = Vec::new();
+        let result = self.generate(&mut watched);
+        for path in watched {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        result
+    }
+
+    /// Generates the parser and writes it, adding every file it depends on to `watched`.
+    fn generate(&self, watched: &mut Vec<PathBuf>) -> Result<PathBuf, Error> {
+        watched.push(self.grammar.clone());
+        let templates = match &self.template_dir {
+            Some(template_dir) => {
+                watched.push(template_dir.clone());
+                watched.extend(Templates::FILE_NAMES.iter().map(|file_name| template_dir.join(file_name)));
+                Templates::with_overrides(template_dir)?
+            }
+            None => Templates::new(),
+        };
+
+        let out_path = self.out_path()?;
+        let grammar_name = self.grammar.display().to_string();
+
+        let gramspec = crate::load_grammar_file(&self.grammar).map_err(|err| match err {
+            Error::Io(err) => Error::Io(io::Error::new(
+                err.kind(),
+                format!("could not read grammar '{}': {}", grammar_name, err),
+            )),
+            Error::Grammar(message) => Error::Grammar(format!("{}: {}", grammar_name, message)),
+            other => other,
+        })?;
+        watched.extend(gramspec.sources.iter().skip(1).cloned());
+        let code = crate::generate_with_templates(&gramspec, templates, &self.parser_name, &self.indent).map_err(|err| match err {
+            Error::Validation(message) => Error::Validation(format!("{}: {}", grammar_name, message)),
+            Error::Generation(message) => Error::Generation(format!("{}: {}", grammar_name, message)),
+            other => other,
+        })?;
+
+        // Leave the file untouched if nothing changed, so dependents are not rebuilt
+        if fs::read_to_string(&out_path).ok().as_deref() != Some(code.as_str()) {
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&out_path, code)?;
+        }
+
+        Ok(out_path)
+    }
+
+    /// Like [`Builder::run`], but reports errors on stderr and exits the build script,
+    /// failing the build with a readable message.
+    pub fn run_or_exit(self) -> PathBuf {
+        let grammar = self.grammar.clone();
+        self.run().unwrap_or_else(|err| {
+            eprintln!("error: failed to generate a parser from '{}'", grammar.display());
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    }
+
+    fn out_path(&self) -> Result<PathBuf, Error> {
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => env::var_os("OUT_DIR").map(PathBuf::from).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "OUT_DIR is not set; call this from a build script or set an output directory",
+                )
+            })?,
+        };
+
+        let out_file = match &self.out_file {
+            Some(out_file) => out_file.clone(),
+            None => {
+                let stem = self.grammar.file_stem().unwrap_or_default();
+                PathBuf::from(stem).with_extension("rs")
+            }
+        };
+
+        Ok(out_dir.join(out_file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAMMAR_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/grammars/include");
+
+    #[test]
+    fn the_parser_is_written_with_the_template_overrides_and_its_sources_are_watched() {
+        let directory = env::temp_dir().join(format!("gramspec-build-{}", std::process::id()));
+        let template_dir = directory.join("templates");
+        fs::create_dir_all(&template_dir).unwrap();
+        let parser_template = format!("// Generated with a custom template\n{}", Templates::new().parser);
+        fs::write(template_dir.join("parser.txt"), parser_template).unwrap();
+
+        let grammar = Path::new(GRAMMAR_DIR).join("main.grm");
+        let builder = compile(&grammar)
+            .parser_name("MainParser")
+            .template_dir(&template_dir)
+            .out_dir(directory.join("out"))
+            .out_file("parsers/main.rs");
+        let mut watched = Vec::new();
+        let result = builder.generate(&mut watched);
+
+        let code = fs::read_to_string(directory.join("out/parsers/main.rs"));
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(result.unwrap(), directory.join("out/parsers/main.rs"));
+        let code = code.unwrap();
+        assert!(code.starts_with("// Generated with a custom template\n"));
+        assert!(code.contains("pub struct MainParser"));
+
+        let mut expected = vec![grammar, template_dir.clone()];
+        expected.extend(Templates::FILE_NAMES.iter().map(|file_name| template_dir.join(file_name)));
+        let included = ["lexical.grm", "values.grm"].map(|name| fs::canonicalize(Path::new(GRAMMAR_DIR).join(name)).unwrap());
+        expected.extend(included);
+        assert_eq!(watched, expected);
+    }
+
+    #[test]
+    fn the_output_file_defaults_to_the_grammar_name() {
+        let builder = compile("grammars/calc.grm").out_dir("out");
+        assert_eq!(builder.out_path().unwrap(), Path::new("out").join("calc.rs"));
+    }
+
+    #[test]
+    fn the_grammar_is_watched_even_if_it_cannot_be_read() {
+        let mut watched = Vec::new();
+        let result = compile("missing.grm").out_dir(env::temp_dir()).generate(&mut watched);
+        assert!(matches!(result, Err(Error::Io(_))));
+        assert_eq!(watched, [PathBuf::from("missing.grm")]);
+    }
+}
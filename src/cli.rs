@@ -0,0 +1,170 @@
+use std::error::Error;
+
+/// Exit code used when a command finished successfully.
+pub const EXIT_SUCCESS: i32 = 0;
+/// Exit code used when the input file is rejected by the grammar.
+pub const EXIT_INPUT_REJECTED: i32 = 1;
+/// Exit code used when the command line arguments are invalid.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code used when the grammar specification is invalid.
+pub const EXIT_GRAMMAR_ERROR: i32 = 3;
+/// Exit code used when a file could not be read or written.
+pub const EXIT_IO_ERROR: i32 = 4;
+
+pub const USAGE: &str = "\
+Usage: gramspec-parser-generator <command> [options]
+
+Commands:
+    generate <grammar> [-o <file>] [--name <ParserName>] [--indent <str>] [--templates <dir>]
+        Generate a parser for the grammar and write it to <file> or stdout.
+    parse <grammar> <input> [--recover]
+        Parse <input> with the grammar and print the resulting tree.
+    check <grammar>
+        Parse and validate the grammar without generating any code.
+
+Options:
+    -o, --output <file>    File the generated parser is written to (default: stdout)
+    -n, --name <name>      Name of the generated parser struct (default: GramspecParser)
+    -i, --indent <str>     Indentation used in the generated code (default: four spaces)
+    -t, --templates <dir>  Directory with template files overriding the built-in ones
+    -r, --recover          Recover from errors while parsing, printing the partial tree
+                           and every error found
+    -h, --help             Print this message
+
+Exit codes:
+    0  success
+    1  the input was rejected by the grammar
+    2  invalid command line arguments
+    3  the grammar specification is invalid
+    4  a file could not be read or written";
+
+/// A command parsed from the command line arguments.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Generate {
+        grammar: String,
+        output: Option<String>,
+        parser_name: String,
+        indent: String,
+        templates: Option<String>,
+    },
+    Parse {
+        grammar: String,
+        input: String,
+        recover: bool,
+    },
+    Check {
+        grammar: String,
+    },
+    Help,
+}
+
+impl Command {
+    /// Parses a command from the given arguments, excluding the program name.
+    pub fn from_args(args: &[String]) -> Result<Command, Box<dyn Error>> {
+        let mut args = args.iter();
+        let command = match args.next() {
+            Some(command) => command.as_str(),
+            None => return Err("No command given".into()),
+        };
+
+        let mut positional: Vec<String> = Vec::new();
+        let mut output: Option<String> = None;
+        let mut templates: Option<String> = None;
+        let mut parser_name = String::from(gramspec::DEFAULT_PARSER_NAME);
+        let mut indent = String::from(gramspec::DEFAULT_INDENT);
+        let mut recover = false;
+
+        if matches!(command, "-h" | "--help" | "help") {
+            return Ok(Command::Help);
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-o" | "--output" => output = Some(Self::option_value(arg, args.next())?),
+                "-n" | "--name" => parser_name = Self::option_value(arg, args.next())?,
+                "-i" | "--indent" => indent = Self::option_value(arg, args.next())?,
+                "-t" | "--templates" => templates = Some(Self::option_value(arg, args.next())?),
+                "-r" | "--recover" => recover = true,
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("Unknown option '{}'", arg).into());
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let command = match command {
+            "generate" => {
+                let [grammar] = Self::positional::<1>(command, positional)?;
+                Command::Generate { grammar, output, parser_name, indent, templates }
+            }
+            "parse" => {
+                let [grammar, input] = Self::positional::<2>(command, positional)?;
+                Command::Parse { grammar, input, recover }
+            }
+            "check" => {
+                let [grammar] = Self::positional::<1>(command, positional)?;
+                Command::Check { grammar }
+            }
+            _ => return Err(format!("Unknown command '{}'", command).into()),
+        };
+
+        Ok(command)
+    }
+
+    fn option_value(option: &str, value: Option<&String>) -> Result<String, Box<dyn Error>> {
+        value
+            .cloned()
+            .ok_or_else(|| format!("Option '{}' requires a value", option).into())
+    }
+
+    fn positional<const N: usize>(command: &str, positional: Vec<String>) -> Result<[String; N], Box<dyn Error>> {
+        let count = positional.len();
+        positional.try_into().map_err(|_| {
+            format!("The {} command expects {} argument(s), found {}", command, N, count).into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Command::from_args(&args).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn options_are_read_after_the_command() {
+        assert_eq!(
+            parse(&["generate", "calc.grm", "-o", "calc.rs", "--name", "CalcParser"]),
+            Ok(Command::Generate {
+                grammar: "calc.grm".to_string(),
+                output: Some("calc.rs".to_string()),
+                parser_name: "CalcParser".to_string(),
+                indent: gramspec::DEFAULT_INDENT.to_string(),
+                templates: None,
+            })
+        );
+        assert_eq!(
+            parse(&["parse", "calc.grm", "input.txt", "-r"]),
+            Ok(Command::Parse { grammar: "calc.grm".to_string(), input: "input.txt".to_string(), recover: true })
+        );
+        assert_eq!(parse(&["check", "calc.grm", "--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported() {
+        assert_eq!(parse(&[]), Err("No command given".to_string()));
+        assert_eq!(parse(&["compile", "calc.grm"]), Err("Unknown command 'compile'".to_string()));
+        assert_eq!(parse(&["generate"]), Err("The generate command expects 1 argument(s), found 0".to_string()));
+        assert_eq!(
+            parse(&["parse", "calc.grm"]),
+            Err("The parse command expects 2 argument(s), found 1".to_string())
+        );
+        assert_eq!(parse(&["generate", "calc.grm", "-o"]), Err("Option '-o' requires a value".to_string()));
+        assert_eq!(parse(&["check", "calc.grm", "--fast"]), Err("Unknown option '--fast'".to_string()));
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::io;
+
+use crate::gramspec_parser::diagnostic::Diagnostics;
+
+/// Errors returned by the public library API.
+#[derive(Debug)]
+pub enum Error {
+    /// A grammar, template or output file could not be read or written.
+    Io(io::Error),
+    /// The grammar specification could not be tokenized or parsed.
+    Grammar(String),
+    /// The grammar specification has errors, found while loading or validating it, each reported
+    /// with the source it points at.
+    Diagnostics(Diagnostics),
+    /// The grammar specification parsed, but cannot be turned into a parser.
+    Validation(String),
+    /// Code generation failed.
+    Generation(String),
+}
+
+impl Error {
+    /// Wraps an internal error, keeping IO errors distinguishable from the given fallback.
+    pub(crate) fn from_boxed(err: Box<dyn std::error::Error>, fallback: fn(String) -> Error) -> Error {
+        let err = match err.downcast::<io::Error>() {
+            Ok(io_error) => return Error::Io(*io_error),
+            Err(err) => err,
+        };
+        match err.downcast::<Diagnostics>() {
+            Ok(diagnostics) => Error::Diagnostics(*diagnostics),
+            Err(err) => fallback(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Grammar(message) => write!(f, "{}", message),
+            Error::Diagnostics(diagnostics) => write!(f, "{}", diagnostics),
+            Error::Validation(message) => write!(f, "{}", message),
+            Error::Generation(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -1,321 +1,474 @@
-use std::error::Error;
-use std::io::Write;
-
-use crate::gramspec_parser::gramspec::GramSpec;
-use crate::gramspec_parser::gramspec::expression::Expression;
-
-pub struct Generator {
-    gramspec: GramSpec,
-}
-
-impl Generator {
-    pub fn new(gramspec: GramSpec) -> Self {
-        Generator { gramspec }
-    }
-
-    pub fn generate(&self, output_file: &str, parser_name: &str, tab_string: &str) -> Result<(), Box<dyn Error>> {
-        // Read templates
-        let parser_template = std::fs::read_to_string("./templates/parser.txt")?;
-        let rule_cases = self.generate_rule_cases()?;
-        let rule_functions = self.generate_rule_functions()?;
-        let meta_rule_functions = self.generate_meta_rule_functions()?;
-        let discard_rule_functions = self.generate_discard_rule_functions()?;
-        let node_types = self.node_types();
-        let ignore_between = self.generate_ignore_between()?;
-
-        // Initialize contents
-        let mut contents = String::new();
-
-        // Add parser template to contents
-        contents.push_str(&parser_template);
-
-        // Replace placeholders
-        contents = contents.replace("_PARSERNAME_", parser_name);
-        contents = contents.replace("_ENTRYRULE_", &self.gramspec.config.entry_rule);
-        contents = contents.replace("_RULECASES_", &rule_cases);
-        contents = contents.replace("_RULEFUNCTIONS_", &rule_functions);
-        contents = contents.replace("_METARULEFUNCTIONS_", &meta_rule_functions);
-        contents = contents.replace("_DISCARDRULEFUNCTIONS_", &discard_rule_functions);
-        contents = contents.replace("_PASCALCASERULENAMES_", &node_types);
-        contents = contents.replace("_IGNOREBETWEEN_", &ignore_between);
-        contents = contents.replace("_IGNOREBETWEENLENGTH_", &self.gramspec.config.ignore_between.len().to_string());
-        contents = contents.replace("_TS_", tab_string); // Replace tab spaces
-
-        // Write to output file
-        let file = std::fs::File::create(output_file)?;
-        let mut writer = std::io::BufWriter::new(file);
-        writer.write_all(contents.as_bytes())?;
-
-        Ok(())
-    }
-
-    fn generate_rule_cases(&self) -> Result<String, Box<dyn Error>> {
-        let rule_case_regular_template =
-            std::fs::read_to_string("./templates/rule_case_regular.txt")?;
-        let rule_case_circular_template =
-            std::fs::read_to_string("./templates/rule_case_circular.txt")?;
-
-        // Generate rule cases
-        let mut rule_cases = String::new();
-        for i in 0..self.gramspec.rules.keys().len() {
-            let rule = self.gramspec.rules.keys().nth(i).unwrap();
-            if self.gramspec.is_left_circular(rule) {
-                rule_cases.push_str(&rule_case_circular_template.replace("_RULENAME_", rule));
-            } else {
-                rule_cases.push_str(&rule_case_regular_template.replace("_RULENAME_", rule));
-            }
-            rule_cases.push('\n');
-        }
-        for i in 0..self.gramspec.meta_rules.keys().len() {
-            let rule = self.gramspec.meta_rules.keys().nth(i).unwrap();
-            if self.gramspec.is_left_circular(rule) {
-                rule_cases.push_str(&rule_case_circular_template.replace("_RULENAME_", rule));
-            } else {
-                rule_cases.push_str(&rule_case_regular_template.replace("_RULENAME_", rule));
-            }
-            rule_cases.push('\n');
-        }
-        for i in 0..self.gramspec.discard_rules.keys().len() {
-            let rule = self.gramspec.discard_rules.keys().nth(i).unwrap();
-            if self.gramspec.is_left_circular(rule) {
-                rule_cases.push_str(&rule_case_circular_template.replace("_RULENAME_", rule));
-            } else {
-                rule_cases.push_str(&rule_case_regular_template.replace("_RULENAME_", rule));
-            }
-            rule_cases.push('\n');
-        }
-        Ok(rule_cases)
-    }
-
-    fn generate_rule_functions(&self) -> Result<String, Box<dyn Error>> {
-        let rule_function_template = std::fs::read_to_string("./templates/rule_function.txt")?;
-
-        // Generate rule functions
-        let mut rule_functions = String::new();
-        for i in 0..self.gramspec.rules.keys().len() {
-            let rule = self.gramspec.rules.keys().nth(i).unwrap();
-            let token_expression = self
-                .gramspec
-                .rules
-                .get(rule)
-                .or_else(|| self.gramspec.meta_rules.get(rule))
-                .ok_or_else(|| format!("Rule '{}' not found", rule))?;
-
-            let mut expressions = String::from("");
-            for i in 0..token_expression.len() {
-                let expression = &token_expression[i];
-                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
-                if i < token_expression.len() - 1 {
-                    expressions.push_str("\n");
-                }
-            }
-
-            rule_functions.push_str(
-                &rule_function_template
-                    .replace("_RULENAME_", &format!("{}", rule))
-                    .replace("_EXPRESSIONS_", &expressions)
-                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string())
-                    .replace("_PASCALCASERULENAME_", &Self::to_pascal_case(rule)),
-            );
-            if i < self.gramspec.rules.keys().len() - 1 {
-                rule_functions.push_str("\n\n");
-            }
-        }
-
-        Ok(rule_functions)
-    }
-
-    fn generate_meta_rule_functions(&self) -> Result<String, Box<dyn Error>> {
-        let rule_function_template = std::fs::read_to_string("./templates/meta_rule_function.txt")?;
-
-        // Generate rule functions
-        let mut rule_functions = String::new();
-        for i in 0..self.gramspec.meta_rules.keys().len() {
-            let rule = self.gramspec.meta_rules.keys().nth(i).unwrap();
-            let token_expression = self
-                .gramspec
-                .rules
-                .get(rule)
-                .or_else(|| self.gramspec.meta_rules.get(rule))
-                .ok_or_else(|| format!("Rule '{}' not found", rule))?;
-
-            let mut expressions = String::from("");
-            for i in 0..token_expression.len() {
-                let expression = &token_expression[i];
-                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
-                if i < token_expression.len() - 1 {
-                    expressions.push_str("\n");
-                }
-            }
-
-            rule_functions.push_str(
-                &rule_function_template
-                    .replace("_RULENAME_", &format!("{}", rule))
-                    .replace("_EXPRESSIONS_", &expressions)
-                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string()),
-            );
-            if i < self.gramspec.meta_rules.keys().len() - 1 {
-                rule_functions.push_str("\n\n");
-            }
-        }
-
-        Ok(rule_functions)
-    }
-
-    fn generate_discard_rule_functions(&self) -> Result<String, Box<dyn Error>> {
-        let rule_function_template = std::fs::read_to_string("./templates/discard_rule_function.txt")?;
-
-        // Generate rule functions
-        let mut rule_functions = String::new();
-        for i in 0..self.gramspec.discard_rules.keys().len() {
-            let rule = self.gramspec.discard_rules.keys().nth(i).unwrap();
-            let token_expression = self
-                .gramspec
-                .rules
-                .get(rule)
-                .or_else(|| self.gramspec.discard_rules.get(rule))
-                .ok_or_else(|| format!("Rule '{}' not found", rule))?;
-
-            let mut expressions = String::from("");
-            for i in 0..token_expression.len() {
-                let expression = &token_expression[i];
-                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
-                if i < token_expression.len() - 1 {
-                    expressions.push_str("\n");
-                }
-            }
-
-            rule_functions.push_str(
-                &rule_function_template
-                    .replace("_RULENAME_", &format!("{}", rule))
-                    .replace("_EXPRESSIONS_", &expressions)
-                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string()),
-            );
-            if i < self.gramspec.discard_rules.keys().len() - 1 {
-                rule_functions.push_str("\n\n");
-            }
-        }
-
-        Ok(rule_functions)
-    }
-
-    fn generate_ignore_between(&self) -> Result<String, Box<dyn Error>> {
-        let mut result = String::new();
-        for token in &self.gramspec.config.ignore_between {
-            if !result.is_empty() {
-                result.push_str(",\n");
-            }
-            result.push_str(&format!("_TS__TS__TS_r#\"{}\"#", token));
-        }
-        Ok(result)
-    }
-
-    fn to_conditional(&self, expression: &Expression, tab_depth: usize) -> Result<String, Box<dyn Error>> {
-        let tab_string: String = "_TS_".repeat(tab_depth);
-        match expression {
-            Expression::RuleName(name) => Ok(format!("_TS_Rule(\"{}\")", name.value)),
-            Expression::Keyword(keyword) => Ok(format!("_TS_Keyword(\"{}\")", keyword.value)),
-            Expression::RegexLiteral(regex) => Ok(format!("_TS_RegexLiteral(r#\"^{}\"#)", regex.value)),
-            Expression::StringLiteral(string) => {
-                if string.value == "\"" {
-                    Ok(format!("_TS_StringLiteral(\"\\\"\")"))
-                } else if string.value == "\\" {
-                    Ok(format!("_TS_StringLiteral(\"\\\\\")"))
-                } else if string.value == "\n" {
-                    Ok(format!("_TS_StringLiteral(\"\\n\")"))
-                } else if string.value == "\t" {
-                    Ok(format!("_TS_StringLiteral(\"\\t\")"))
-                } else {
-                    Ok(format!("_TS_StringLiteral(\"{}\")", string.value))
-                }
-            }
-            Expression::Discard(expr) => Ok(format!(
-                "Expression::discard(\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(expr, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::Meta(expr) => Ok(format!(
-                "Expression::meta(\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(expr, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::Or(left, right) => Ok(format!(
-                "Expression::or(\n{}{},\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(left, tab_depth + 1)?,
-                tab_string,
-                self.to_conditional(right, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::And(left, right) => Ok(format!(
-                "Expression::and(\n{}{},\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(left, tab_depth + 1)?,
-                tab_string,
-                self.to_conditional(right, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::DelimitRepeatOne(left, right) => Ok(format!(
-                "Expression::delimit_repeat_one(\n{}{},\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(left, tab_depth + 1)?,
-                tab_string,
-                self.to_conditional(right, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::DelimitRepeatZero(left, right) => Ok(format!(
-                "Expression::delimit_repeat_zero(\n{}{},\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(left, tab_depth + 1)?,
-                tab_string,
-                self.to_conditional(right, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::Optional(expr) => Ok(format!(
-                "Expression::optional(\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(expr, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::RepeatOne(expr) => Ok(format!(
-                "Expression::repeat_one(\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(expr, tab_depth + 1)?,
-                tab_string,
-            )),
-            Expression::RepeatZero(expr) => Ok(format!(
-                "Expression::repeat_zero(\n{}{}\n{})",
-                tab_string,
-                self.to_conditional(expr, tab_depth + 1)?,
-                tab_string,
-            )),
-        }
-    }
-
-    fn node_types(&self) -> String {
-        let mut result = String::new();
-        for i in 0..self.gramspec.rules.keys().len() {
-            let rule = self.gramspec.rules.keys().nth(i).unwrap();
-            if i < self.gramspec.rules.keys().len() - 1 {
-                result.push_str(&format!("_TS__TS_{},\n", Self::to_pascal_case(rule)));
-            } else {
-                result.push_str(&format!("_TS__TS_{},", Self::to_pascal_case(rule)));
-            }
-        }
-        result
-    }
-
-    fn to_pascal_case(s: &str) -> String {
-        s.split('_')
-            .map(|word| {
-                let mut chars = word.chars();
-                match chars.next() {
-                    None => String::new(),
-                    Some(f) => f.to_uppercase().collect::<String>() + chars.as_str(),
-                }
-            })
-            .collect()
-}
-}
+pub mod templates;
+
+use std::error::Error;
+use std::io::Write;
+
+use crate::gramspec_parser::gramspec::{GramSpec, anchored_regex};
+use crate::gramspec_parser::gramspec::expression::Expression;
+use crate::gramspec_parser::gramspec::precedence::PrecedenceLevel;
+use templates::Templates;
+
+pub struct Generator<'a> {
+    gramspec: &'a GramSpec,
+    templates: Templates,
+}
+
+impl<'a> Generator<'a> {
+    /// Creates a generator using the default templates.
+    pub fn new(gramspec: &'a GramSpec) -> Self {
+        Self::with_templates(gramspec, Templates::new())
+    }
+
+    /// Creates a generator using the given templates.
+    pub fn with_templates(gramspec: &'a GramSpec, templates: Templates) -> Self {
+        Generator { gramspec, templates }
+    }
+
+    /// Generates the parser and writes it to the given file.
+    pub fn generate(&self, output_file: &str, parser_name: &str, tab_string: &str) -> Result<(), Box<dyn Error>> {
+        let file = std::fs::File::create(output_file)?;
+        let mut writer = std::io::BufWriter::new(file);
+        self.write_to(&mut writer, parser_name, tab_string)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Generates the parser and writes it to the given writer.
+    pub fn write_to(&self, writer: &mut impl Write, parser_name: &str, tab_string: &str) -> Result<(), Box<dyn Error>> {
+        let contents = self.generate_string(parser_name, tab_string)?;
+        writer.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Generates the parser and returns its source code.
+    pub fn generate_string(&self, parser_name: &str, tab_string: &str) -> Result<String, Box<dyn Error>> {
+        let rule_cases = self.generate_rule_cases()?;
+        let rule_functions = self.generate_rule_functions()?;
+        let meta_rule_functions = self.generate_meta_rule_functions()?;
+        let discard_rule_functions = self.generate_discard_rule_functions()?;
+        let node_types = self.node_types();
+        let ignore_between = self.generate_ignore_between()?;
+        let keywords = self.generate_keywords();
+        let recovery_cases = self.generate_recovery_cases()?;
+
+        // Initialize contents
+        let mut contents = String::new();
+
+        // Add parser template to contents
+        contents.push_str(&self.templates.parser);
+
+        // Replace placeholders
+        contents = contents.replace("_PARSERNAME_", parser_name);
+        contents = contents.replace("_ENTRYRULE_", &self.gramspec.config.entry_rule);
+        contents = contents.replace("_RULECASES_", &rule_cases);
+        contents = contents.replace("_RULEFUNCTIONS_", &rule_functions);
+        contents = contents.replace("_METARULEFUNCTIONS_", &meta_rule_functions);
+        contents = contents.replace("_DISCARDRULEFUNCTIONS_", &discard_rule_functions);
+        contents = contents.replace("_PASCALCASERULENAMES_", &node_types);
+        contents = contents.replace("_KEYWORDS_", &keywords);
+        contents = contents.replace("_RECOVERYCASES_", &recovery_cases);
+        contents = contents.replace("_IGNOREBETWEEN_", &ignore_between);
+        contents = contents.replace("_IGNOREBETWEENLENGTH_", &self.gramspec.config.ignore_between.len().to_string());
+        contents = contents.replace("_TS_", tab_string); // Replace tab spaces
+
+        Ok(contents)
+    }
+
+    fn generate_rule_cases(&self) -> Result<String, Box<dyn Error>> {
+        let rule_case_regular_template = &self.templates.rule_case_regular;
+        let rule_case_circular_template = &self.templates.rule_case_circular;
+
+        // Generate rule cases, in the order the rules are declared
+        let mut rule_cases = String::new();
+        let mut rules: Vec<&String> = self.gramspec.rules.keys()
+            .chain(self.gramspec.meta_rules.keys())
+            .chain(self.gramspec.discard_rules.keys())
+            .collect();
+        rules.sort_by_key(|rule| self.gramspec.declaration_key(rule));
+        for rule in rules {
+            if self.gramspec.is_left_circular(rule) {
+                rule_cases.push_str(&rule_case_circular_template.replace("_RULENAME_", rule));
+            } else {
+                rule_cases.push_str(&rule_case_regular_template.replace("_RULENAME_", rule));
+            }
+            rule_cases.push('\n');
+        }
+        Ok(rule_cases)
+    }
+
+    fn generate_rule_functions(&self) -> Result<String, Box<dyn Error>> {
+        let rule_function_template = &self.templates.rule_function;
+
+        // Generate rule functions
+        let mut rule_functions = String::new();
+        let rules = self.gramspec.in_declaration_order(&self.gramspec.rules);
+        for (i, &(rule, token_expression)) in rules.iter().enumerate() {
+            let mut expressions = String::from("");
+            for i in 0..token_expression.len() {
+                let expression = &token_expression[i];
+                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
+                if i < token_expression.len() - 1 {
+                    expressions.push('\n');
+                }
+            }
+
+            // Precedence rules parse their operand with the expressions, then climb the operators
+            let (template, operators, operators_length) = match self.gramspec.precedence.get(rule) {
+                Some(levels) => (
+                    &self.templates.precedence_rule_function,
+                    self.generate_operators(levels)?,
+                    levels.iter().map(|level| level.operators.len()).sum::<usize>(),
+                ),
+                None => (rule_function_template, String::new(), 0),
+            };
+
+            rule_functions.push_str(
+                &template
+                    .replace("_RULENAME_", &rule.to_string())
+                    .replace("_EXPRESSIONS_", &expressions)
+                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string())
+                    .replace("_OPERATORS_", &operators)
+                    .replace("_OPERATORSLENGTH_", &operators_length.to_string())
+                    .replace("_PASCALCASERULENAME_", &Self::to_pascal_case(rule)),
+            );
+            if i < rules.len() - 1 {
+                rule_functions.push_str("\n\n");
+            }
+        }
+
+        Ok(rule_functions)
+    }
+
+    fn generate_meta_rule_functions(&self) -> Result<String, Box<dyn Error>> {
+        let rule_function_template = &self.templates.meta_rule_function;
+
+        // Generate rule functions
+        let mut rule_functions = String::new();
+        let rules = self.gramspec.in_declaration_order(&self.gramspec.meta_rules);
+        for (i, &(rule, token_expression)) in rules.iter().enumerate() {
+            let mut expressions = String::from("");
+            for i in 0..token_expression.len() {
+                let expression = &token_expression[i];
+                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
+                if i < token_expression.len() - 1 {
+                    expressions.push('\n');
+                }
+            }
+
+            rule_functions.push_str(
+                &rule_function_template
+                    .replace("_RULENAME_", &rule.to_string())
+                    .replace("_EXPRESSIONS_", &expressions)
+                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string()),
+            );
+            if i < rules.len() - 1 {
+                rule_functions.push_str("\n\n");
+            }
+        }
+
+        Ok(rule_functions)
+    }
+
+    fn generate_discard_rule_functions(&self) -> Result<String, Box<dyn Error>> {
+        let rule_function_template = &self.templates.discard_rule_function;
+
+        // Generate rule functions
+        let mut rule_functions = String::new();
+        let rules = self.gramspec.in_declaration_order(&self.gramspec.discard_rules);
+        for (i, &(rule, token_expression)) in rules.iter().enumerate() {
+            let mut expressions = String::from("");
+            for i in 0..token_expression.len() {
+                let expression = &token_expression[i];
+                expressions.push_str(&format!("_TS__TS__TS_{},", self.to_conditional(expression, 3)?));
+                if i < token_expression.len() - 1 {
+                    expressions.push('\n');
+                }
+            }
+
+            rule_functions.push_str(
+                &rule_function_template
+                    .replace("_RULENAME_", &rule.to_string())
+                    .replace("_EXPRESSIONS_", &expressions)
+                    .replace("_EXPRESSIONSLENGTH_", &token_expression.len().to_string()),
+            );
+            if i < rules.len() - 1 {
+                rule_functions.push_str("\n\n");
+            }
+        }
+
+        Ok(rule_functions)
+    }
+
+    fn generate_operators(&self, levels: &[PrecedenceLevel]) -> Result<String, Box<dyn Error>> {
+        let mut result = String::new();
+        for (level_index, level) in levels.iter().enumerate() {
+            for operator in &level.operators {
+                if !result.is_empty() {
+                    result.push('\n');
+                }
+                result.push_str(&format!(
+                    "_TS__TS__TS_Operator::new({}, Associativity::{:?}, {}),",
+                    level_index,
+                    level.associativity,
+                    self.to_conditional(operator, 3)?.trim_start_matches("_TS_"),
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    fn generate_recovery_cases(&self) -> Result<String, Box<dyn Error>> {
+        let mut result = String::new();
+        for (rule, expressions) in self.gramspec.in_declaration_order(&self.gramspec.recovery) {
+            result.push_str(&format!("_TS__TS__TS_\"{}\" => Some(vec![\n", rule));
+            for expression in expressions {
+                result.push_str(&format!("_TS__TS__TS__TS_{},\n", self.to_conditional(expression, 4)?));
+            }
+            result.push_str("_TS__TS__TS_]),\n");
+        }
+        Ok(result)
+    }
+
+    fn generate_ignore_between(&self) -> Result<String, Box<dyn Error>> {
+        let mut result = String::new();
+        for token in &self.gramspec.config.ignore_between {
+            if !result.is_empty() {
+                result.push_str(",\n");
+            }
+            result.push_str(&format!("_TS__TS__TS_r#\"{}\"#", anchored_regex(token)));
+        }
+        Ok(result)
+    }
+
+    fn generate_keywords(&self) -> String {
+        let mut result = String::new();
+        for (name, value) in self.gramspec.in_declaration_order(&self.gramspec.keywords) {
+            result.push_str(&format!("_TS_({:?}, {:?}),\n", name, value));
+        }
+        result
+    }
+
+    fn to_conditional(&self, expression: &Expression, tab_depth: usize) -> Result<String, Box<dyn Error>> {
+        let tab_string: String = "_TS_".repeat(tab_depth);
+        match expression {
+            Expression::RuleName(name) => Ok(format!("_TS_Rule(\"{}\")", name.value)),
+            Expression::Keyword(keyword) => {
+                if self.gramspec.keyword_value(&keyword.value).is_none() {
+                    return Err(format!("Keyword '{}' at {} is not declared", keyword.value, keyword.location()).into());
+                }
+                Ok(format!("_TS_Keyword(\"{}\")", keyword.value))
+            }
+            Expression::EndOfInput(_) => Ok("_TS_Expression::EndOfInput".to_string()),
+            Expression::Cut(_) => Ok("_TS_Expression::Cut".to_string()),
+            Expression::RegexLiteral(regex) => Ok(format!("_TS_RegexLiteral(r#\"{}\"#)", anchored_regex(&regex.value))),
+            Expression::StringLiteral(string) => {
+                if string.value == "\"" {
+                    Ok("_TS_StringLiteral(\"\\\"\")".to_string())
+                } else if string.value == "\\" {
+                    Ok("_TS_StringLiteral(\"\\\\\")".to_string())
+                } else if string.value == "\n" {
+                    Ok("_TS_StringLiteral(\"\\n\")".to_string())
+                } else if string.value == "\t" {
+                    Ok("_TS_StringLiteral(\"\\t\")".to_string())
+                } else {
+                    Ok(format!("_TS_StringLiteral(\"{}\")", string.value))
+                }
+            }
+            Expression::Discard(expr) => Ok(format!(
+                "Expression::discard(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::Meta(expr) => Ok(format!(
+                "Expression::meta(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::Lookahead(expr) => Ok(format!(
+                "Expression::lookahead(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::Label(label, expr) => Ok(format!(
+                "Expression::label(\n{}{:?},\n{}{}\n{})",
+                tab_string,
+                label,
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::NotLookahead(expr) => Ok(format!(
+                "Expression::not_lookahead(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::Or(left, right) => Ok(format!(
+                "Expression::or(\n{}{},\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(left, tab_depth + 1)?,
+                tab_string,
+                self.to_conditional(right, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::And(left, right) => Ok(format!(
+                "Expression::and(\n{}{},\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(left, tab_depth + 1)?,
+                tab_string,
+                self.to_conditional(right, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::DelimitRepeatOne(left, right) => Ok(format!(
+                "Expression::delimit_repeat_one(\n{}{},\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(left, tab_depth + 1)?,
+                tab_string,
+                self.to_conditional(right, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::DelimitRepeatZero(left, right) => Ok(format!(
+                "Expression::delimit_repeat_zero(\n{}{},\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(left, tab_depth + 1)?,
+                tab_string,
+                self.to_conditional(right, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::DelimitRepeatRange(left, right, min, max) => Ok(format!(
+                "Expression::delimit_repeat_range(\n{}{},\n{}{},\n{}{},\n{}{:?}\n{})",
+                tab_string,
+                self.to_conditional(left, tab_depth + 1)?,
+                tab_string,
+                self.to_conditional(right, tab_depth + 1)?,
+                tab_string,
+                min,
+                tab_string,
+                max,
+                tab_string,
+            )),
+            Expression::RepeatRange(expr, min, max) => Ok(format!(
+                "Expression::repeat_range(\n{}{},\n{}{},\n{}{:?}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+                min,
+                tab_string,
+                max,
+                tab_string,
+            )),
+            Expression::Optional(expr) => Ok(format!(
+                "Expression::optional(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::RepeatOne(expr) => Ok(format!(
+                "Expression::repeat_one(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+            Expression::RepeatZero(expr) => Ok(format!(
+                "Expression::repeat_zero(\n{}{}\n{})",
+                tab_string,
+                self.to_conditional(expr, tab_depth + 1)?,
+                tab_string,
+            )),
+        }
+    }
+
+    fn node_types(&self) -> String {
+        let mut result = String::new();
+        let rules = self.gramspec.in_declaration_order(&self.gramspec.rules);
+        for (i, &(rule, _)) in rules.iter().enumerate() {
+            if i < rules.len() - 1 {
+                result.push_str(&format!("_TS__TS_{},\n", Self::to_pascal_case(rule)));
+            } else {
+                result.push_str(&format!("_TS__TS_{},", Self::to_pascal_case(rule)));
+            }
+        }
+        result
+    }
+
+    pub(crate) fn to_pascal_case(s: &str) -> String {
+        s.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    None => String::new(),
+                    Some(f) => f.to_uppercase().collect::<String>() + chars.as_str(),
+                }
+            })
+            .collect()
+}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Declares rules of every kind, with keywords and recovery rules, in an order other than
+    /// alphabetical.
+    const GRAMMAR: &str = "\
+@entry_rule: 'statements'
+statements: statement+ ~end
+statement: KW_PRINT value ';' | KW_LET assignment ';'
+$assignment: name '=' value
+value: number | name
+~end: r'\\s*'
+number: r'[0-9]+'
+name: r'[a-z]+'
+@recover statement: ';'
+@recover value: >';'
+KW_PRINT: 'print'
+KW_LET: 'let'
+";
+
+    fn generate() -> String {
+        let gramspec = crate::load_grammar(GRAMMAR).unwrap();
+        crate::validate(&gramspec).unwrap();
+        Generator::new(&gramspec).generate_string("OrderParser", "    ").unwrap()
+    }
+
+    /// Returns the positions of the texts in the code, failing if one of them is not found.
+    fn positions(code: &str, texts: &[&str]) -> Vec<usize> {
+        texts.iter().map(|text| code.find(text).unwrap_or_else(|| panic!("{} is not generated", text))).collect()
+    }
+
+    #[test]
+    fn generated_code_is_the_same_for_every_load_of_the_grammar() {
+        let first = generate();
+        for _ in 0..5 {
+            assert!(generate() == first, "the generated code changed between loads of the same grammar");
+        }
+    }
+
+    #[test]
+    fn definitions_are_generated_in_declaration_order() {
+        let code = generate();
+        let node_types = &code[code.find("pub enum NodeType {").unwrap()..];
+        let variants = positions(node_types, &["Statements,", "Statement,", "Value,", "Number,", "Name,"]);
+        assert!(variants.is_sorted(), "{}", &node_types[..node_types.find('}').unwrap()]);
+
+        let arms = positions(
+            &code,
+            &[
+                "\"statements\" => self._statements(",
+                "\"statement\" => self._statement(",
+                "\"assignment\" => self._assignment(",
+                "\"value\" => self._value(",
+                "\"end\" => self._end(",
+                "\"number\" => self._number(",
+                "\"name\" => self._name(",
+            ],
+        );
+        assert!(arms.is_sorted());
+
+        assert!(positions(&code, &["\"statement\" => Some(vec![", "\"value\" => Some(vec!["]).is_sorted());
+        assert!(positions(&code, &["(\"KW_PRINT\", \"print\")", "(\"KW_LET\", \"let\")"]).is_sorted());
+    }
+}
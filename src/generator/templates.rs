@@ -0,0 +1,113 @@
+use std::io;
+use std::path::Path;
+
+/// The code templates the generator fills in.
+///
+/// The default templates are compiled into the crate. Individual templates can be replaced
+/// by files with the same name in a user-supplied directory.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    pub parser: String,
+    pub rule_case_regular: String,
+    pub rule_case_circular: String,
+    pub rule_function: String,
+    pub meta_rule_function: String,
+    pub discard_rule_function: String,
+    pub precedence_rule_function: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Templates {
+    /// File names of the templates, as looked up in an override directory.
+    pub const FILE_NAMES: &'static [&'static str] = &[
+        "parser.txt",
+        "rule_case_regular.txt",
+        "rule_case_circular.txt",
+        "rule_function.txt",
+        "meta_rule_function.txt",
+        "discard_rule_function.txt",
+        "precedence_rule_function.txt",
+    ];
+
+    /// Creates the default templates embedded in the crate.
+    pub fn new() -> Self {
+        Templates {
+            parser: include_str!("../../templates/parser.txt").to_string(),
+            rule_case_regular: include_str!("../../templates/rule_case_regular.txt").to_string(),
+            rule_case_circular: include_str!("../../templates/rule_case_circular.txt").to_string(),
+            rule_function: include_str!("../../templates/rule_function.txt").to_string(),
+            meta_rule_function: include_str!("../../templates/meta_rule_function.txt").to_string(),
+            discard_rule_function: include_str!("../../templates/discard_rule_function.txt").to_string(),
+            precedence_rule_function: include_str!("../../templates/precedence_rule_function.txt").to_string(),
+        }
+    }
+
+    /// Creates the default templates, replacing each one that has a file of the same name
+    /// in `directory`.
+    pub fn with_overrides<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
+        let directory = directory.as_ref();
+        if !directory.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("template directory '{}' does not exist", directory.display()),
+            ));
+        }
+
+        let mut templates = Self::new();
+        for file_name in Self::FILE_NAMES {
+            let path = directory.join(file_name);
+            if path.is_file() {
+                *templates.get_mut(file_name) = std::fs::read_to_string(&path)?;
+            }
+        }
+        Ok(templates)
+    }
+
+    fn get_mut(&mut self, file_name: &str) -> &mut String {
+        match file_name {
+            "parser.txt" => &mut self.parser,
+            "rule_case_regular.txt" => &mut self.rule_case_regular,
+            "rule_case_circular.txt" => &mut self.rule_case_circular,
+            "rule_function.txt" => &mut self.rule_function,
+            "meta_rule_function.txt" => &mut self.meta_rule_function,
+            "discard_rule_function.txt" => &mut self.discard_rule_function,
+            "precedence_rule_function.txt" => &mut self.precedence_rule_function,
+            _ => unreachable!("unknown template file: {}", file_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn files_in_the_directory_replace_the_templates_of_the_same_name() {
+        let directory = std::env::temp_dir().join(format!("gramspec-templates-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("rule_function.txt"), "custom rule function").unwrap();
+        fs::write(directory.join("unknown.txt"), "not a template").unwrap();
+        let templates = Templates::with_overrides(&directory);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let templates = templates.unwrap();
+        let defaults = Templates::new();
+        assert_eq!(templates.rule_function, "custom rule function");
+        assert_eq!(templates.parser, defaults.parser);
+        assert_eq!(templates.meta_rule_function, defaults.meta_rule_function);
+    }
+
+    #[test]
+    fn a_missing_directory_is_reported() {
+        let err = Templates::with_overrides("no/such/templates").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "template directory 'no/such/templates' does not exist");
+    }
+}
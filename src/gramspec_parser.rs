@@ -1,3 +1,4 @@
 pub mod token;
 pub mod parser;
-pub mod gramspec;
\ No newline at end of file
+pub mod gramspec;
+pub mod diagnostic;
\ No newline at end of file
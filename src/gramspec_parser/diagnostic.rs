@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::gramspec_parser::token::Token;
+
+/// How serious a diagnostic is. Only errors stop a grammar specification from loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a grammar specification, located at the source it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The grammar file the problem is in, if the grammar was read from a file.
+    pub file: Option<Arc<PathBuf>>,
+    /// Byte offset where the source the diagnostic points at starts.
+    pub start: usize,
+    /// Byte offset where the source the diagnostic points at ends.
+    pub end: usize,
+    pub line: usize,
+    /// Column of `start` in characters, counting from 1.
+    pub column: usize,
+    /// Column of `start` in UTF-16 code units, counting from 1, as editors and the language server
+    /// protocol count them.
+    pub column_utf16: usize,
+    /// The line of the grammar containing `start`, shown under the message when known.
+    pub source_line: Option<String>,
+    /// Additional context, such as where a duplicate was first defined.
+    pub notes: Vec<String>,
+    /// A suggestion for fixing the problem.
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates an error pointing at the source from `start` to `end`, which starts at `line` and
+    /// `column`.
+    pub fn error_at(message: impl Into<String>, start: usize, end: usize, line: usize, column: usize) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            file: None,
+            start,
+            end,
+            line,
+            column,
+            column_utf16: column,
+            source_line: None,
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    /// Creates an error pointing at a token.
+    pub fn error(message: impl Into<String>, token: &Token) -> Self {
+        let mut diagnostic = Self::error_at(message, token.position, token.position + token.length, token.line, token.column);
+        diagnostic.file = token.file.clone();
+        diagnostic
+    }
+
+    /// Creates a warning pointing at a token.
+    pub fn warning(message: impl Into<String>, token: &Token) -> Self {
+        Diagnostic { severity: Severity::Warning, ..Self::error(message, token) }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Describes where the problem is, as `file:line:column` when the file is known.
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}:{}", file.display(), self.line, self.column),
+            None => format!("line {}, column {}", self.line, self.column),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Renders the diagnostic with the source line it points at, underlined with carets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        write!(f, "{}--> {}", gutter, self.location())?;
+        if let Some(source_line) = &self.source_line {
+            // The span is in bytes, but the carets have to be counted in characters
+            let underlined = source_line
+                .char_indices()
+                .nth(self.column - 1)
+                .map_or("", |(offset, _)| &source_line[offset..])
+                .char_indices()
+                .take_while(|(offset, _)| *offset < self.end - self.start)
+                .count();
+            let width = underlined.max(1);
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", number, source_line)?;
+            write!(f, "\n{} | {}{}", gutter, " ".repeat(self.column - 1), "^".repeat(width))?;
+        }
+        for note in &self.notes {
+            write!(f, "\n{} = note: {}", gutter, note)?;
+        }
+        if let Some(help) = &self.help {
+            write!(f, "\n{} = help: {}", gutter, help)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// Every problem found in a grammar specification, returned together so that they can all be
+/// fixed in one go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Returns the number of diagnostics that are errors.
+    pub fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
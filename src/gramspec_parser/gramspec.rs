@@ -1,15 +1,61 @@
+pub mod analysis;
 pub mod gramspec_config;
 pub mod expression;
+pub mod precedence;
 
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::gramspec_parser::diagnostic::{Diagnostic, Diagnostics};
 use crate::gramspec_parser::gramspec::expression::Expression;
 use crate::gramspec_parser::gramspec::gramspec_config::GramSpecConfig;
+use crate::gramspec_parser::gramspec::precedence::PrecedenceLevel;
+use crate::gramspec_parser::token::Token;
+use crate::gramspec_parser::token::token_type::TokenType;
+
+/// Keyword that matches the end of the input, after any trailing ignore_between trivia.
+pub const END_OF_INPUT_KEYWORD: &str = "ENDMARKER";
+
+/// Anchors a regex of a grammar to the start of the remaining input, as the interpreter and the
+/// generated parsers match it. The pattern is grouped so that a top-level `|` is anchored too.
+pub fn anchored_regex(pattern: &str) -> String {
+    format!("^(?:{})", pattern)
+}
 
 pub struct GramSpec {
     pub rules: HashMap<String, Vec<Expression>>,
     pub config: GramSpecConfig,
     pub meta_rules: HashMap<String, Vec<Expression>>,
     pub discard_rules: HashMap<String, Vec<Expression>>,
+    /// Precedence tables of the rules declared with `@precedence`. The operand of such a rule is
+    /// stored in `rules`, like the alternatives of any other rule.
+    pub precedence: HashMap<String, Vec<PrecedenceLevel>>,
+    /// Synchronisation expressions of the rules declared with `@recover`. When such a rule fails
+    /// after matching part of the input, a recovering parse skips to the end of the next match of
+    /// one of them.
+    pub recovery: HashMap<String, Vec<Expression>>,
+    /// Keywords declared by the grammar, mapped to the text they match.
+    pub keywords: HashMap<String, String>,
+    /// The name token of every rule, meta rule, discard rule and keyword, used to report where it is defined.
+    pub definitions: HashMap<String, Token>,
+    /// The name token of every `@recover` declaration, used to report where it is declared.
+    pub recovery_definitions: HashMap<String, Token>,
+    /// The grammar files this specification was read from, starting with the including file.
+    pub sources: Vec<PathBuf>,
+    /// The text of every grammar this specification was read from, with its file, starting with the
+    /// including grammar. A grammar read from a string has no file. Used to show the source line a
+    /// problem points at.
+    pub texts: Vec<(Option<Arc<PathBuf>>, String)>,
+}
+
+impl Default for GramSpec {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GramSpec {
@@ -19,6 +65,13 @@ pub fn new() -> Self {
             config: GramSpecConfig::new(),
             meta_rules: HashMap::new(),
             discard_rules: HashMap::new(),
+            precedence: HashMap::new(),
+            recovery: HashMap::new(),
+            keywords: HashMap::new(),
+            definitions: HashMap::new(),
+            recovery_definitions: HashMap::new(),
+            sources: Vec::new(),
+            texts: Vec::new(),
         }
     }
 
@@ -47,6 +100,233 @@ pub fn add_discard_rule(&mut self, name: String, expressions: Vec<Expression>) {
         self.discard_rules.insert(name, expressions);
     }
 
+    pub fn add_precedence_rule(&mut self, name: String, operand: Vec<Expression>, levels: Vec<PrecedenceLevel>) {
+        self.rules.insert(name.clone(), operand);
+        self.precedence.insert(name, levels);
+    }
+
+    pub fn add_recovery(&mut self, name: String, expressions: Vec<Expression>) {
+        self.recovery.insert(name, expressions);
+    }
+
+    pub fn add_keyword(&mut self, name: String, value: String) {
+        self.keywords.insert(name, value);
+    }
+
+    /// Returns the text matched by a declared keyword.
+    pub fn keyword_value(&self, name: &str) -> Option<&str> {
+        self.keywords.get(name).map(String::as_str)
+    }
+
+    /// Returns the entries of one of the maps of the specification, such as `rules`, in the order
+    /// their names are defined.
+    pub fn in_declaration_order<'a, V>(&self, map: &'a HashMap<String, V>) -> Vec<(&'a String, &'a V)> {
+        let mut entries: Vec<(&String, &V)> = map.iter().collect();
+        entries.sort_by_key(|(name, _)| self.declaration_key(name));
+        entries
+    }
+
+    /// Returns a key sorting names in the order they are defined: file by file, in the order the
+    /// files were read, then by position. Names without a recorded definition come last, sorted by
+    /// name.
+    pub(crate) fn declaration_key<'a>(&self, name: &'a str) -> (bool, Option<usize>, usize, &'a str) {
+        match self.definitions.get(name) {
+            Some(token) => {
+                let file = token.file.as_ref();
+                let source = file.and_then(|file| self.sources.iter().position(|source| source == file.as_ref()));
+                (false, source, token.position, name)
+            }
+            None => (true, None, 0, name),
+        }
+    }
+
+    /// Records where a rule is defined, failing if a rule with the same name was already defined.
+    pub fn define(&mut self, name: &Token) -> Result<(), Box<Diagnostic>> {
+        if let Some(previous) = self.definitions.get(&name.value) {
+            return Err(Diagnostic::error(format!("'{}' is defined twice", name.value), name)
+                .with_note(format!("it is first defined at {}", previous.location()))
+                .into());
+        }
+        self.definitions.insert(name.value.clone(), name.clone());
+        Ok(())
+    }
+
+    /// Adds the rules, ignore_between patterns and sources of an included grammar specification.
+    /// Other configuration options are taken from the including grammar only.
+    pub fn merge(&mut self, other: GramSpec) -> Result<(), Box<Diagnostic>> {
+        let mut definitions: Vec<&Token> = other.definitions.values().collect();
+        definitions.sort_by_key(|token| (token.file.clone(), token.position));
+        for name in definitions {
+            // The included text is not merged yet, so the line the duplicate is on comes from it
+            self.define(name).map_err(|diagnostic| Box::new(other.with_source_line(*diagnostic)))?;
+        }
+
+        self.rules.extend(other.rules);
+        self.meta_rules.extend(other.meta_rules);
+        self.discard_rules.extend(other.discard_rules);
+        self.precedence.extend(other.precedence);
+        self.recovery.extend(other.recovery);
+        self.recovery_definitions.extend(other.recovery_definitions);
+        self.keywords.extend(other.keywords);
+
+        if let Some(tokens) = other.config.definitions.get("ignore_between") {
+            let definitions = self.config.definitions.entry("ignore_between".to_string()).or_default();
+            definitions.extend(tokens.iter().cloned());
+        }
+        for pattern in other.config.ignore_between {
+            if !self.config.ignore_between.contains(&pattern) {
+                self.config.ignore_between.push(pattern);
+            }
+        }
+        for source in other.sources {
+            if !self.sources.contains(&source) {
+                self.sources.push(source);
+            }
+        }
+        self.texts.extend(other.texts);
+        Ok(())
+    }
+
+    /// Checks that the grammar specification can be turned into a parser. Every problem found is
+    /// returned as a diagnostic pointing at the source it is about, in source order.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let mut diagnostics = Vec::new();
+        if !self.rules.contains_key(&self.config.entry_rule) {
+            let entry_rule = &self.config.entry_rule;
+            let message = format!("Entry rule '{}' is not defined", entry_rule);
+            diagnostics.push(match self.config.definitions.get("entry_rule").and_then(|tokens| tokens.last()) {
+                Some(token) => Diagnostic::error(message, token).with_help(format!("define it with `{}: ...`", entry_rule)),
+                // The default entry rule is not set anywhere, so the start of the grammar is pointed at
+                None => self.error_at_start(message).with_help(format!(
+                    "define a rule named '{}', or name another rule with `@entry_rule: 'name'`",
+                    entry_rule
+                )),
+            });
+        }
+        let mut recovered_rules: Vec<&String> = self.recovery.keys().collect();
+        recovered_rules.sort();
+        for rule in recovered_rules.into_iter().filter(|rule| self.get_expression(rule).is_none()) {
+            let message = format!("Recovery is declared for '{}', which is not a rule", rule);
+            let diagnostic = match self.recovery_definitions.get(rule) {
+                Some(token) => Diagnostic::error(message, token),
+                None => self.error_at_start(message),
+            };
+            diagnostics.push(diagnostic.with_help(format!("define '{}' with `{}: ...`, or remove the recovery", rule, rule)));
+        }
+
+        for token in self.undefined_rules() {
+            let message = format!("Rule '{}' is not defined", token.value);
+            diagnostics.push(Diagnostic::error(message, token).with_help(format!("define it with `{}: ...`", token.value)));
+        }
+        for (token, err) in self.invalid_regexes() {
+            // Syntax errors show the pattern with a caret over several lines, ending with the reason
+            let err = err.to_string();
+            let reason = err.lines().last().unwrap_or_default().trim_start_matches("error: ");
+            diagnostics.push(Diagnostic::error(format!("Invalid regex: {}", reason), token));
+        }
+        for token in self.undeclared_keywords() {
+            let message = format!("Keyword '{}' is not declared", token.value);
+            diagnostics.push(Diagnostic::error(message, token).with_help(format!("declare it with `{}: '...'`", token.value)));
+        }
+
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.file.clone(), diagnostic.start));
+        let diagnostics = diagnostics.into_iter().map(|diagnostic| self.with_source_line(diagnostic)).collect();
+        Err(Box::new(Diagnostics { diagnostics }))
+    }
+
+    /// Creates an error pointing at the start of the including grammar, for a problem that is not
+    /// about any part of it.
+    fn error_at_start(&self, message: String) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error_at(message, 0, 0, 1, 1);
+        diagnostic.file = self.texts.first().and_then(|(file, _)| file.clone());
+        diagnostic
+    }
+
+    /// Adds the source line a diagnostic points at, and its column in UTF-16 code units, from the
+    /// text of its grammar.
+    fn with_source_line(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let Some((_, text)) = self.texts.iter().find(|(file, _)| *file == diagnostic.file) else {
+            return diagnostic;
+        };
+        let start = diagnostic.start.min(text.len());
+        let line_start = text[..start].rfind(['\r', '\n']).map_or(0, |line_break| line_break + 1);
+        let line = text[line_start..].split(['\r', '\n']).next().unwrap_or_default();
+        diagnostic.source_line = Some(line.to_string());
+        diagnostic.column_utf16 = text[line_start..start].encode_utf16().count() + 1;
+        diagnostic
+    }
+
+    /// Returns every expression of the grammar, including the operators of precedence tables and
+    /// the synchronisation expressions of recoveries, with the sub-expressions of each.
+    fn all_expressions(&self) -> Vec<&Expression> {
+        let mut expressions = Vec::new();
+        let mut pending: Vec<&Expression> = self
+            .rules
+            .values()
+            .chain(self.meta_rules.values())
+            .chain(self.discard_rules.values())
+            .flatten()
+            .chain(self.precedence.values().flatten().flat_map(|level| &level.operators))
+            .chain(self.recovery.values().flatten())
+            .collect();
+        while let Some(expression) = pending.pop() {
+            pending.extend(expression.sub_expressions());
+            expressions.push(expression);
+        }
+        expressions
+    }
+
+    /// Returns the uses of keywords that are not declared, in source order.
+    pub fn undeclared_keywords(&self) -> Vec<&Token> {
+        let mut undeclared: Vec<&Token> = self
+            .all_expressions()
+            .into_iter()
+            .filter_map(|expression| match expression {
+                Expression::Keyword(token) if self.keyword_value(&token.value).is_none() => Some(token),
+                _ => None,
+            })
+            .collect();
+        undeclared.sort_by_key(|token| (token.file.clone(), token.position));
+        undeclared
+    }
+
+    /// Returns the references to rules that are not defined, in source order.
+    pub fn undefined_rules(&self) -> Vec<&Token> {
+        let mut undefined: Vec<&Token> = self
+            .all_expressions()
+            .into_iter()
+            .filter_map(|expression| match expression {
+                Expression::RuleName(token) if self.get_expression(&token.value).is_none() => Some(token),
+                _ => None,
+            })
+            .collect();
+        undefined.sort_by_key(|token| (token.file.clone(), token.position));
+        undefined
+    }
+
+    /// Returns the regex literals and ignore_between patterns that do not compile, with the reason,
+    /// in source order.
+    pub fn invalid_regexes(&self) -> Vec<(&Token, regex::Error)> {
+        let ignore_between = self.config.definitions.get("ignore_between").into_iter().flatten();
+        let patterns = self
+            .all_expressions()
+            .into_iter()
+            .filter_map(|expression| match expression {
+                Expression::RegexLiteral(token) => Some(token),
+                _ => None,
+            })
+            .chain(ignore_between.filter(|token| token.token_type == TokenType::RegexLiteral));
+        // Patterns are compiled as the interpreter and the generated parsers compile them
+        let mut invalid: Vec<(&Token, regex::Error)> = patterns
+            .filter_map(|token| Regex::new(&anchored_regex(&token.value)).err().map(|err| (token, err)))
+            .collect();
+        invalid.sort_by_key(|(token, _)| (token.file.clone(), token.position));
+        invalid
+    }
+
     pub fn is_left_circular(&self, rule_name: &str) -> bool {
         if let Some(expressions) = self.get_expression(rule_name) {
             for expr in expressions {
@@ -91,6 +371,8 @@ fn is_left_circular_expression(
             Expression::StringLiteral(_) |
             Expression::RegexLiteral(_) |
             Expression::Keyword(_) => false,
+            Expression::EndOfInput(_) => false,
+            Expression::Cut(_) => false,
 
             Expression::Discard(inner) => {
                 self.is_left_circular_expression(original_rule, inner, visited)
@@ -98,6 +380,9 @@ fn is_left_circular_expression(
             Expression::Meta(inner) => {
                 self.is_left_circular_expression(original_rule, inner, visited)
             }
+            Expression::Lookahead(inner) | Expression::NotLookahead(inner) | Expression::Label(_, inner) => {
+                self.is_left_circular_expression(original_rule, inner, visited)
+            }
             Expression::And(left, _right) => {
                 self.is_left_circular_expression(original_rule, left, visited)
             }
@@ -126,6 +411,89 @@ fn is_left_circular_expression(
             Expression::DelimitRepeatOne(expr, _delim) => {
                 self.is_left_circular_expression(original_rule, expr, visited)
             }
+
+            Expression::RepeatRange(inner, _min, _max) => {
+                self.is_left_circular_expression(original_rule, inner, visited)
+            }
+
+            Expression::DelimitRepeatRange(expr, _delim, _min, _max) => {
+                self.is_left_circular_expression(original_rule, expr, visited)
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gramspec_parser::diagnostic::Diagnostics;
+    use crate::gramspec_parser::parser::Parser;
+
+    /// Validates the grammar and returns its problems as `message at line:column`, with the source
+    /// line and help of each.
+    fn problems(grammar: &str) -> Vec<(String, Option<String>, Option<String>)> {
+        let gramspec = Parser::new(grammar.to_string()).parse().unwrap();
+        let err = gramspec.validate().expect_err("the grammar should not be valid");
+        let diagnostics = err.downcast_ref::<Diagnostics>().expect("problems are returned as diagnostics");
+        diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let location = format!("{} at {}:{}", diagnostic.message, diagnostic.line, diagnostic.column);
+                (location, diagnostic.source_line.clone(), diagnostic.help.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_problem_is_reported_where_it_is() {
+        let grammar = "start: a KW_X r'[' missing\n@recover nothing: ';'\na: 'ä' | r'(x'\n";
+        let first_line = Some("start: a KW_X r'[' missing".to_string());
+        assert_eq!(
+            problems(grammar),
+            [
+                (
+                    "Entry rule 'file' is not defined at 1:1".to_string(),
+                    first_line.clone(),
+                    Some("define a rule named 'file', or name another rule with `@entry_rule: 'name'`".to_string())
+                ),
+                (
+                    "Keyword 'KW_X' is not declared at 1:10".to_string(),
+                    first_line.clone(),
+                    Some("declare it with `KW_X: '...'`".to_string())
+                ),
+                ("Invalid regex: unclosed character class at 1:15".to_string(), first_line.clone(), None),
+                (
+                    "Rule 'missing' is not defined at 1:20".to_string(),
+                    first_line,
+                    Some("define it with `missing: ...`".to_string())
+                ),
+                (
+                    "Recovery is declared for 'nothing', which is not a rule at 2:10".to_string(),
+                    Some("@recover nothing: ';'".to_string()),
+                    Some("define 'nothing' with `nothing: ...`, or remove the recovery".to_string())
+                ),
+                ("Invalid regex: unclosed group at 3:10".to_string(), Some("a: 'ä' | r'(x'".to_string()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_undefined_entry_rule_is_reported_where_it_is_set() {
+        let problems = problems("@entry_rule: 'top'\nstart: 'x'\n");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0, "Entry rule 'top' is not defined at 1:14");
+    }
+
+    #[test]
+    fn source_lines_end_at_every_kind_of_line_break() {
+        let problems = problems("file: a b\r\na: missing\rb: 'b'\r");
+        assert_eq!(
+            problems,
+            [(
+                "Rule 'missing' is not defined at 2:4".to_string(),
+                Some("a: missing".to_string()),
+                Some("define it with `missing: ...`".to_string())
+            )]
+        );
+    }
+}
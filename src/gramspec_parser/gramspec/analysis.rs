@@ -0,0 +1,206 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::gramspec_parser::diagnostic::Diagnostic;
+use crate::gramspec_parser::gramspec::{GramSpec, anchored_regex};
+use crate::gramspec_parser::gramspec::expression::Expression;
+use crate::gramspec_parser::gramspec::precedence::Associativity;
+
+/// Something the input matched by an expression can start with.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Terminal {
+    /// A string literal, by its text.
+    String(String),
+    /// A regex literal, by its pattern.
+    Regex(String),
+    /// A keyword, by its name.
+    Keyword(String),
+    /// The end of the input.
+    EndOfInput,
+}
+
+/// Which rules of a grammar specification can match without consuming any input (are nullable),
+/// and which terminals the input they consume can start with (their FIRST sets).
+///
+/// Lookaheads, cuts and the end of input consume nothing, so they are nullable, and lookaheads and
+/// cuts add nothing to FIRST sets.
+///
+/// ```
+/// use gramspec::{Analysis, Terminal};
+///
+/// let grammar = "@entry_rule: 'list'\nlist: item*\nitem: sign? r'[0-9]+' | ('x'?)+\nsign: '-'\n";
+/// let gramspec = gramspec::load_grammar(grammar)?;
+/// let analysis = Analysis::new(&gramspec);
+/// assert!(analysis.is_nullable("item") && !analysis.is_nullable("sign"));
+/// let first: Vec<Terminal> = analysis.first("item").unwrap().iter().cloned().collect();
+/// assert_eq!(first, [Terminal::String("-".into()), Terminal::String("x".into()), Terminal::Regex("[0-9]+".into())]);
+///
+/// // `item*` and `('x'?)+` repeat expressions that can match nothing
+/// assert_eq!(analysis.nullable_repetitions().len(), 2);
+/// # Ok::<(), gramspec::Error>(())
+/// ```
+pub struct Analysis<'a> {
+    gramspec: &'a GramSpec,
+    nullable: HashSet<String>,
+    first: HashMap<String, BTreeSet<Terminal>>,
+    /// Patterns of the regex literals that match the empty string.
+    empty_regexes: HashSet<String>,
+}
+
+impl<'a> Analysis<'a> {
+    /// Analyses every rule, meta rule and discard rule of the grammar specification.
+    pub fn new(gramspec: &'a GramSpec) -> Self {
+        let empty_regexes = gramspec
+            .all_expressions()
+            .into_iter()
+            .filter_map(|expression| match expression {
+                Expression::RegexLiteral(token) => Some(&token.value),
+                _ => None,
+            })
+            .filter(|pattern| Regex::new(&anchored_regex(pattern)).is_ok_and(|regex| regex.is_match("")))
+            .cloned()
+            .collect();
+        let mut analysis = Analysis { gramspec, nullable: HashSet::new(), first: HashMap::new(), empty_regexes };
+
+        // Rules refer to each other, so their sets are grown until none of them changes
+        let rules: Vec<(&String, &Vec<Expression>)> = gramspec
+            .rules
+            .iter()
+            .chain(&gramspec.meta_rules)
+            .chain(&gramspec.discard_rules)
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(name, alternatives) in &rules {
+                let nullable = alternatives.iter().any(|alternative| analysis.is_nullable_expression(alternative));
+                let mut first: BTreeSet<Terminal> =
+                    alternatives.iter().flat_map(|alternative| analysis.first_of(alternative)).collect();
+                // A precedence rule can also start with one of its prefix operators
+                let levels = gramspec.precedence.get(name).into_iter().flatten();
+                for level in levels.filter(|level| level.associativity == Associativity::Prefix) {
+                    first.extend(level.operators.iter().flat_map(|operator| analysis.first_of(operator)));
+                }
+
+                if nullable && analysis.nullable.insert(name.clone()) {
+                    changed = true;
+                }
+                if analysis.first.get(name) != Some(&first) {
+                    analysis.first.insert(name.clone(), first);
+                    changed = true;
+                }
+            }
+        }
+        analysis
+    }
+
+    /// Returns true if the rule can match without consuming any input.
+    pub fn is_nullable(&self, rule_name: &str) -> bool {
+        self.nullable.contains(rule_name)
+    }
+
+    /// Returns the terminals the input matched by the rule can start with, or None if there is no
+    /// such rule.
+    pub fn first(&self, rule_name: &str) -> Option<&BTreeSet<Terminal>> {
+        self.first.get(rule_name)
+    }
+
+    /// Returns true if the expression can match without consuming any input.
+    pub fn is_nullable_expression(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::RuleName(token) => self.is_nullable(&token.value),
+            Expression::StringLiteral(token) => token.value.is_empty(),
+            Expression::RegexLiteral(token) => self.empty_regexes.contains(&token.value),
+            Expression::Keyword(token) => self.gramspec.keyword_value(&token.value).is_some_and(str::is_empty),
+            Expression::EndOfInput(_)
+            | Expression::Cut(_)
+            | Expression::Lookahead(_)
+            | Expression::NotLookahead(_)
+            | Expression::Optional(_)
+            | Expression::RepeatZero(_)
+            | Expression::DelimitRepeatZero(_, _) => true,
+            Expression::Or(left, right) => self.is_nullable_expression(left) || self.is_nullable_expression(right),
+            Expression::And(left, right) => self.is_nullable_expression(left) && self.is_nullable_expression(right),
+            Expression::RepeatRange(expr, min, _) | Expression::DelimitRepeatRange(expr, _, min, _) => {
+                *min == 0 || self.is_nullable_expression(expr)
+            }
+            Expression::RepeatOne(expr)
+            | Expression::DelimitRepeatOne(expr, _)
+            | Expression::Discard(expr)
+            | Expression::Meta(expr)
+            | Expression::Label(_, expr) => self.is_nullable_expression(expr),
+        }
+    }
+
+    /// Returns the terminals the input matched by the expression can start with.
+    pub fn first_of(&self, expression: &Expression) -> BTreeSet<Terminal> {
+        match expression {
+            Expression::RuleName(token) => self.first(&token.value).cloned().unwrap_or_default(),
+            Expression::StringLiteral(token) if token.value.is_empty() => BTreeSet::new(),
+            Expression::StringLiteral(token) => BTreeSet::from([Terminal::String(token.value.clone())]),
+            Expression::RegexLiteral(token) => BTreeSet::from([Terminal::Regex(token.value.clone())]),
+            Expression::Keyword(token) => BTreeSet::from([Terminal::Keyword(token.value.clone())]),
+            Expression::EndOfInput(_) => BTreeSet::from([Terminal::EndOfInput]),
+            Expression::Cut(_) | Expression::Lookahead(_) | Expression::NotLookahead(_) => BTreeSet::new(),
+            Expression::Or(left, right) => {
+                let mut first = self.first_of(left);
+                first.extend(self.first_of(right));
+                first
+            }
+            Expression::And(left, right) => {
+                let mut first = self.first_of(left);
+                if self.is_nullable_expression(left) {
+                    first.extend(self.first_of(right));
+                }
+                first
+            }
+            Expression::Optional(expr)
+            | Expression::RepeatOne(expr)
+            | Expression::RepeatZero(expr)
+            | Expression::RepeatRange(expr, _, _)
+            | Expression::DelimitRepeatOne(expr, _)
+            | Expression::DelimitRepeatZero(expr, _)
+            | Expression::DelimitRepeatRange(expr, _, _, _)
+            | Expression::Discard(expr)
+            | Expression::Meta(expr)
+            | Expression::Label(_, expr) => self.first_of(expr),
+        }
+    }
+
+    /// Returns a warning for every unbounded repetition of an expression that can match without
+    /// consuming any input, in source order. Such a repetition stops at the first empty match
+    /// instead of looping forever, which is rarely what the grammar means.
+    pub fn nullable_repetitions(&self) -> Vec<Diagnostic> {
+        let mut repetitions: Vec<&Expression> = self
+            .gramspec
+            .all_expressions()
+            .into_iter()
+            .filter(|expression| match expression {
+                Expression::RepeatZero(expr) | Expression::RepeatOne(expr) | Expression::RepeatRange(expr, _, None) => {
+                    self.is_nullable_expression(expr)
+                }
+                // A delimited repetition only matches nothing again if both its parts can
+                Expression::DelimitRepeatZero(expr, delimiter)
+                | Expression::DelimitRepeatOne(expr, delimiter)
+                | Expression::DelimitRepeatRange(expr, delimiter, _, None) => {
+                    self.is_nullable_expression(expr) && self.is_nullable_expression(delimiter)
+                }
+                _ => false,
+            })
+            .collect();
+        repetitions.sort_by_key(|expression| {
+            let token = expression.first_token();
+            (token.file.clone(), token.position)
+        });
+
+        repetitions
+            .into_iter()
+            .map(|expression| {
+                let token = expression.first_token();
+                Diagnostic::warning(format!("'{:?}' repeats an expression that can match nothing", expression), token)
+                    .with_help("make the repeated expression consume input, such as by removing a '?' or '*' in it")
+            })
+            .collect()
+    }
+}
@@ -7,6 +7,8 @@ pub enum Expression {
     RegexLiteral(Token),
     StringLiteral(Token),
     Keyword(Token),
+    EndOfInput(Token),
+    Cut(Token),
     Or(Box<Expression>, Box<Expression>),
     And(Box<Expression>, Box<Expression>),
     DelimitRepeatOne(Box<Expression>, Box<Expression>),
@@ -16,6 +18,52 @@ pub enum Expression {
     RepeatZero(Box<Expression>),
     Discard(Box<Expression>),
     Meta(Box<Expression>),
+    RepeatRange(Box<Expression>, usize, Option<usize>),
+    DelimitRepeatRange(Box<Expression>, Box<Expression>, usize, Option<usize>),
+    Lookahead(Box<Expression>),
+    NotLookahead(Box<Expression>),
+    Label(String, Box<Expression>),
+}
+
+impl Expression {
+    /// Returns the sub-expressions of this expression, in order.
+    pub fn sub_expressions(&self) -> Vec<&Expression> {
+        match self {
+            Expression::RuleName(_)
+            | Expression::RegexLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::Keyword(_)
+            | Expression::EndOfInput(_)
+            | Expression::Cut(_) => vec![],
+            Expression::Or(left, right)
+            | Expression::And(left, right)
+            | Expression::DelimitRepeatOne(left, right)
+            | Expression::DelimitRepeatZero(left, right)
+            | Expression::DelimitRepeatRange(left, right, _, _) => vec![left, right],
+            Expression::Optional(expr)
+            | Expression::RepeatOne(expr)
+            | Expression::RepeatZero(expr)
+            | Expression::Discard(expr)
+            | Expression::Meta(expr)
+            | Expression::RepeatRange(expr, _, _)
+            | Expression::Lookahead(expr)
+            | Expression::NotLookahead(expr)
+            | Expression::Label(_, expr) => vec![expr],
+        }
+    }
+
+    /// Returns the token this expression starts with in the grammar.
+    pub fn first_token(&self) -> &Token {
+        match self {
+            Expression::RuleName(token)
+            | Expression::RegexLiteral(token)
+            | Expression::StringLiteral(token)
+            | Expression::Keyword(token)
+            | Expression::EndOfInput(token)
+            | Expression::Cut(token) => token,
+            _ => self.sub_expressions()[0].first_token(),
+        }
+    }
 }
 
 impl fmt::Debug for Expression {
@@ -25,6 +73,8 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             Expression::RegexLiteral(token) => write!(f, "\"{}\"", token.value),
             Expression::StringLiteral(token) => write!(f, "\'{}\'", token.value),
             Expression::Keyword(token) => write!(f, "{}", token.value),
+            Expression::EndOfInput(token) => write!(f, "{}", token.value),
+            Expression::Cut(_) => write!(f, "^"),
             Expression::Or(left, right) => write!(f, "{:?} | {:?}", left, right),
             Expression::And(left, right) => write!(f, "{:?} & {:?}", left, right),
             Expression::DelimitRepeatOne(left, right) => write!(f, "({:?}),({:?})+", left, right),
@@ -34,6 +84,20 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             Expression::RepeatZero(expr) => write!(f, "({:?})*", expr),
             Expression::Discard(expr) => write!(f, "~({:?})", expr),
             Expression::Meta(expr) => write!(f, "$({:?})", expr),
+            Expression::RepeatRange(expr, min, max) => {
+                write!(f, "({:?}){{{},{}}}", expr, min, max.map_or(String::new(), |max| max.to_string()))
+            }
+            Expression::DelimitRepeatRange(left, right, min, max) => write!(
+                f,
+                "({:?}),({:?}){{{},{}}}",
+                left,
+                right,
+                min,
+                max.map_or(String::new(), |max| max.to_string())
+            ),
+            Expression::Lookahead(expr) => write!(f, ">({:?})", expr),
+            Expression::NotLookahead(expr) => write!(f, "!({:?})", expr),
+            Expression::Label(label, expr) => write!(f, "{}=({:?})", label, expr),
         }
     }
 }
\ No newline at end of file
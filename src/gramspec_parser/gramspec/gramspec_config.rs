@@ -1,26 +1,39 @@
-use std::error::Error;
-
-pub struct GramSpecConfig {
-    pub entry_rule: String,
-    pub ignore_between: Vec<String>,
-}
-
-impl GramSpecConfig {
-    pub fn new() -> Self {
-        GramSpecConfig {
-            entry_rule: String::from("file"),
-            ignore_between: Vec::new(),
-        }
-    }
-
-    pub fn set(&mut self, config: String, value: String) -> Result<(), Box<dyn Error>> {
-        match config.as_str() {
-            "entry_rule" => self.entry_rule = value.to_string(),
-            "ignore_between" => {
-                self.ignore_between.push(value.to_string());
-            },
-            _ => return Err(format!("Unknown configuration option: {}", config).into()),
-        }
-        Ok(())
-    }
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::gramspec_parser::token::Token;
+
+pub struct GramSpecConfig {
+    pub entry_rule: String,
+    pub ignore_between: Vec<String>,
+    /// The value tokens of each configuration option, in the order they were set, used to report
+    /// where an option is set.
+    pub definitions: HashMap<String, Vec<Token>>,
+}
+
+impl Default for GramSpecConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GramSpecConfig {
+    pub fn new() -> Self {
+        GramSpecConfig {
+            entry_rule: String::from("file"),
+            ignore_between: Vec::new(),
+            definitions: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, config: String, value: String) -> Result<(), Box<dyn Error>> {
+        match config.as_str() {
+            "entry_rule" => self.entry_rule = value.to_string(),
+            "ignore_between" => {
+                self.ignore_between.push(value.to_string());
+            },
+            _ => return Err(format!("Unknown configuration option: {}", config).into()),
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use crate::gramspec_parser::diagnostic::Diagnostic;
+use crate::gramspec_parser::gramspec::expression::Expression;
+use crate::gramspec_parser::token::Token;
+
+/// How the operators of a precedence level combine with their operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// Binary operators grouped from the left: `a - b - c` is `(a - b) - c`.
+    Left,
+    /// Binary operators grouped from the right: `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+    /// Binary operators that cannot be chained: `a < b < c` is rejected.
+    NonAssoc,
+    /// Unary operators written before their operand.
+    Prefix,
+    /// Unary operators written after their operand.
+    Postfix,
+}
+
+impl Associativity {
+    /// Reads the associativity named at the start of a precedence level, such as `left`.
+    pub fn from_token(token: &Token) -> Result<Self, Box<Diagnostic>> {
+        match token.value.as_str() {
+            "left" => Ok(Associativity::Left),
+            "right" => Ok(Associativity::Right),
+            "nonassoc" => Ok(Associativity::NonAssoc),
+            "prefix" => Ok(Associativity::Prefix),
+            "postfix" => Ok(Associativity::Postfix),
+            _ => Err(Diagnostic::error(format!("Unknown associativity '{}'", token.value), token)
+                .with_help("a precedence level starts with left, right, nonassoc, prefix or postfix")
+                .into()),
+        }
+    }
+
+    /// Returns true if the operators of this associativity take an operand on both sides.
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Associativity::Left | Associativity::Right | Associativity::NonAssoc)
+    }
+}
+
+/// One level of a precedence table. Levels are listed from the loosest to the tightest binding.
+#[derive(Debug, Clone)]
+pub struct PrecedenceLevel {
+    pub associativity: Associativity,
+    /// The operators of the level, each a string literal, regex literal or keyword.
+    pub operators: Vec<Expression>,
+}
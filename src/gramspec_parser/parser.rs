@@ -1,579 +1,1271 @@
-use std::error::Error;
-
-use crate::gramspec_parser::gramspec;
-use crate::gramspec_parser::token;
-use gramspec::{GramSpec, expression::Expression};
-use token::{Token, token_type::TokenType};
-
-use regex::escape;
-
-mod tokenizer;
-
-pub struct Parser {
-    tokenizer: tokenizer::Tokenizer,
-    structures: Vec<Structure>,
-    tokens: Vec<Token>,
-    position: usize,
-}
-
-impl Parser {
-    /// Creates a new Parser with the given tokens.
-    pub fn new(input: String) -> Self {
-        Parser {
-            tokenizer: tokenizer::Tokenizer::new(input),
-            structures: Vec::new(),
-            tokens: Vec::new(),
-            position: 0,
-        }
-    }
-
-    pub fn parse(&mut self) -> Result<GramSpec, Box<dyn Error>> {
-        self.tokens = self.tokenizer.tokenize()?;
-        self.structures = self.structurize()?;
-        let mut rules: Vec<Structure> = Vec::new();
-        let mut config_directives: Vec<Structure> = Vec::new();
-        let mut meta_rules: Vec<Structure> = Vec::new();
-        let mut discard_rules: Vec<Structure> = Vec::new();
-
-        for structure in &self.structures {
-            match structure.structure_type {
-                StructureType::RuleDefinition => rules.push(structure.clone()),
-                StructureType::ConfigDirective => config_directives.push(structure.clone()),
-                StructureType::MetaRuleDefinition => meta_rules.push(structure.clone()),
-                StructureType::DiscardRuleDefinition => discard_rules.push(structure.clone()),
-            }
-        }
-
-        let mut gramspec = GramSpec::new();
-
-        // TODO: Check for rule duplicates and config directive duplicates
-
-        for rule in &rules {
-            let phrase = &rule.tokens[1..];
-            let and_phrase = &self.add_implict_ands(&phrase.to_vec());
-            let expression = self.to_expression(and_phrase.to_vec())?;
-            let alternatives = self.split_into_alternatives(&expression);
-            gramspec.add_rule(rule.tokens[0].value.clone(), alternatives);
-        }
-
-        for meta_rule in &meta_rules {
-            let phrase = &meta_rule.tokens[1..];
-            let and_phrase = &self.add_implict_ands(&phrase.to_vec());
-            let expression = self.to_expression(and_phrase.to_vec())?;
-            let alternatives = self.split_into_alternatives(&expression);
-            gramspec.add_meta_rule(meta_rule.tokens[0].value.clone(), alternatives);
-        }
-
-        for discard_rule in &discard_rules {
-            let phrase = &discard_rule.tokens[1..];
-            let and_phrase = &self.add_implict_ands(&phrase.to_vec());
-            let expression = self.to_expression(and_phrase.to_vec())?;
-            let alternatives = self.split_into_alternatives(&expression);
-            gramspec.add_discard_rule(discard_rule.tokens[0].value.clone(), alternatives);
-        }
-
-        for config_directive in &mut config_directives {
-            let directive_name = &config_directive.tokens[0].value.clone();
-            let value_type = &config_directive.tokens[1].token_type;
-            let directive_value = {
-                if value_type == &TokenType::RegexLiteral {
-                    config_directive.tokens[1].value.clone()
-                } else {
-                    // Escapes the string literal value so that quotes and backslashes are handled correctly
-                    escape(&config_directive.tokens[1].value)
-                }
-            };
-
-            gramspec
-                .config
-                .set(directive_name.clone(), directive_value.clone())?;
-        }
-
-        // Loop through ignore_between rules and remove them from the normal rule lists
-        for ignore_rule in &gramspec.config.ignore_between {
-            gramspec.rules.remove(ignore_rule);
-            gramspec.meta_rules.remove(ignore_rule);
-            gramspec.discard_rules.remove(ignore_rule);
-        }
-
-        Ok(gramspec)
-    }
-
-    fn structurize(&mut self) -> Result<Vec<Structure>, Box<dyn Error>> {
-        let mut structures = Vec::new();
-        while self.position < self.tokens.len() {
-            let mut structure = Structure::new(Vec::new(), StructureType::RuleDefinition);
-            let initial_pos = self.position;
-            let mut longest_pos = 0;
-
-            // Try to parse a config directive
-            if let Some(new_structure) = self.expect_config_directive()? {
-                if self.position > longest_pos {
-                    structure = new_structure;
-                    longest_pos = self.position;
-                }
-            }
-            // Reset position
-            self.position = initial_pos;
-            // Try to parse a rule definition
-            if let Some(new_structure) = self.expect_rule_definition()? {
-                if self.position > longest_pos {
-                    structure = new_structure;
-                    longest_pos = self.position;
-                }
-            }
-            // Reset position
-            self.position = initial_pos;
-            // Try to parse a meta rule definition
-            if let Some(new_structure) = self.expect_meta_rule_definition()? {
-                if self.position > longest_pos {
-                    structure = new_structure;
-                    longest_pos = self.position;
-                }
-            }
-
-            // Reset position
-            self.position = initial_pos;
-            // Try to parse a discard rule definition
-            if let Some(new_structure) = self.expect_discard_rule_definition()? {
-                if self.position > longest_pos {
-                    structure = new_structure;
-                    longest_pos = self.position;
-                }
-            }
-
-            if longest_pos == 0 {
-                let (line, column) = self
-                    .tokenizer
-                    .get_line_column(self.tokens[self.position].position);
-                return Err(format!(
-                    "Unexpected token at position {}:{}: {:?}",
-                    line, column, self.tokens[self.position].token_type
-                )
-                .into());
-            }
-
-            // Set position to the end of the structure
-            self.position = longest_pos;
-
-            structures.push(structure);
-        }
-        Ok(structures)
-    }
-
-    fn expect_config_directive(&mut self) -> Result<Option<Structure>, Box<dyn Error>> {
-        let mut structure = Structure::new(Vec::new(), StructureType::ConfigDirective);
-
-        // Read the config directive token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::ConfigDirective {
-            self.position += 1;
-        } else {
-            return Ok(None);
-        }
-
-        // Read the config directive name token
-        if self.tokens[self.position].token_type == TokenType::RuleName {
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected config directive name at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read colon token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected ':' at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read the config directive value token
-        match self.tokens[self.position].token_type {
-            TokenType::StringLiteral | TokenType::RegexLiteral => {
-                structure.tokens.push(self.tokens[self.position].clone());
-                self.position += 1;
-            }
-            _ => {
-                return Err(format!(
-                    "Expected string or regex value at position {}, found {:?}",
-                    self.tokens[self.position].position, self.tokens[self.position].token_type
-                )
-                .into());
-            }
-        }
-
-        // Read endline token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::Newline {
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected endline at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        Ok(Some(structure))
-    }
-
-    fn expect_rule_definition(&mut self) -> Result<Option<Structure>, Box<dyn Error>> {
-        let mut structure = Structure::new(Vec::new(), StructureType::RuleDefinition);
-
-        // Read the rule name token
-        if self.tokens[self.position].token_type == TokenType::RuleName {
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        } else {
-            return Ok(None);
-        }
-
-        // Read the rule definition token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected ':' at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read tokens until we reach a newline or end of input
-        while self.position < self.tokens.len() {
-            if self.tokens[self.position].token_type == TokenType::Newline {
-                self.position += 1;
-                break;
-            }
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        }
-
-        Ok(Some(structure))
-    }
-
-    fn expect_meta_rule_definition(&mut self) -> Result<Option<Structure>, Box<dyn Error>> {
-        let mut structure = Structure::new(Vec::new(), StructureType::MetaRuleDefinition);
-
-        // Read the meta rule token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::Meta {
-            self.position += 1;
-        } else {
-            return Ok(None);
-        }
-
-        // Read the meta rule name token
-        if self.tokens[self.position].token_type == TokenType::RuleName {
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected meta rule name at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read the rule definition token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected ':' at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read tokens until we reach a newline or end of input
-        while self.position < self.tokens.len() {
-            if self.tokens[self.position].token_type == TokenType::Newline {
-                self.position += 1;
-                break;
-            }
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        }
-
-        Ok(Some(structure))
-    }
-
-    fn expect_discard_rule_definition(&mut self) -> Result<Option<Structure>, Box<dyn Error>> {
-        let mut structure = Structure::new(Vec::new(), StructureType::DiscardRuleDefinition);
-
-        // Read the discard rule token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::Discard {
-            self.position += 1;
-        } else {
-            return Ok(None);
-        }
-
-        // Read the discard rule name token
-        if self.tokens[self.position].token_type == TokenType::RuleName {
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected discard rule name at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read the rule definition token, don't bother adding it to the structure
-        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
-            self.position += 1;
-        } else {
-            return Err(format!(
-                "Expected ':' at position {}, found {:?}",
-                self.tokens[self.position].position, self.tokens[self.position].token_type
-            )
-            .into());
-        }
-
-        // Read tokens until we reach a newline or end of input
-        while self.position < self.tokens.len() {
-            if self.tokens[self.position].token_type == TokenType::Newline {
-                self.position += 1;
-                break;
-            }
-            structure.tokens.push(self.tokens[self.position].clone());
-            self.position += 1;
-        }
-
-        Ok(Some(structure))
-    }
-
-    fn add_implict_ands(&self, tokens: &Vec<Token>) -> Vec<Token> {
-        let mut final_ = Vec::new();
-        for i in 0..(tokens.len() - 1) {
-            let token = &tokens[i];
-            let next_token = &tokens[i + 1];
-
-            final_.push(token.clone());
-
-            if token.token_type == TokenType::OpenParen {
-                continue;
-            }
-            if token.token_type == TokenType::DelimitRepeat {
-                continue;
-            }
-            if token.token_type == TokenType::Or {
-                continue;
-            }
-            if token.token_type == TokenType::Discard {
-                continue;
-            }
-            if token.token_type == TokenType::Meta {
-                continue;
-            }
-
-            if next_token.token_type == TokenType::CloseParen {
-                continue;
-            }
-            if next_token.token_type.is_operator()
-                && !matches!(next_token.token_type, TokenType::Discard | TokenType::Meta)
-            {
-                continue;
-            }
-
-            let (line, column) = self.tokenizer.get_line_column(token.position);
-
-            final_.push(Token {
-                value: String::from("&"),
-                token_type: TokenType::And,
-                position: token.position + 1,
-                line,
-                column: column + 1,
-            });
-        }
-
-        final_.push(tokens[tokens.len() - 1].clone());
-
-        final_
-    }
-
-    fn to_expression(&self, tokens: Vec<Token>) -> Result<Expression, Box<dyn Error>> {
-        // Conversion from infix to postfix notation
-
-        let mut postfix: Vec<Token> = Vec::new();
-        let mut stack: Vec<Token> = Vec::new();
-
-        for token in tokens {
-            if token.token_type.is_unary_operator()
-                && !matches!(token.token_type, TokenType::Discard | TokenType::Meta)
-            {
-                if !stack.is_empty()
-                    && stack.last().unwrap().token_type == TokenType::DelimitRepeat
-                    && (token.token_type == TokenType::RepeatOne
-                        || token.token_type == TokenType::RepeatZero)
-                {
-                    postfix.push(stack.pop().unwrap());
-                }
-                postfix.push(token);
-            } else if token.token_type.is_operator() {
-                if stack.is_empty() {
-                    stack.push(token);
-                } else {
-                    while let Some(top) = stack.last() {
-                        if top.token_type.is_operator() {
-                            let top_prec = top.token_type.get_precedence();
-                            let token_prec = token.token_type.get_precedence();
-                            let is_right_associative =
-                                matches!(token.token_type, TokenType::Discard | TokenType::Meta);
-
-                            if (is_right_associative && top_prec > token_prec)
-                                || (!is_right_associative && top_prec >= token_prec)
-                            {
-                                postfix.push(stack.pop().unwrap());
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    stack.push(token);
-                }
-            } else if token.token_type == TokenType::OpenParen {
-                stack.push(token);
-            } else if token.token_type == TokenType::CloseParen {
-                while let Some(top) = stack.pop() {
-                    if top.token_type == TokenType::OpenParen {
-                        break;
-                    }
-                    postfix.push(top);
-                }
-            } else {
-                postfix.push(token);
-            }
-        }
-
-        while stack.len() > 0 {
-            let top = stack.pop().unwrap();
-            if top.token_type == TokenType::OpenParen {
-                return Err(format!("Unmatched '(' at position {}", top.position).into());
-            }
-            postfix.push(top);
-        }
-
-        // Conversion from postfix to expression
-
-        if postfix.len() == 1 {
-            let token = postfix[0].clone();
-            match token.token_type {
-                TokenType::RuleName => return Ok(Expression::RuleName(token)),
-                TokenType::RegexLiteral => return Ok(Expression::RegexLiteral(token)),
-                TokenType::StringLiteral => return Ok(Expression::StringLiteral(token)),
-                TokenType::Keyword => return Ok(Expression::Keyword(token)),
-                _ => {}
-            }
-        }
-
-        let mut operands: Vec<Expression> = Vec::new();
-
-        let mut i = 0;
-        while i < postfix.len() {
-            let token = &postfix[i];
-            let expression = match token.token_type {
-                // Literal/Identifier tokens
-                TokenType::RuleName => Expression::RuleName(token.clone()),
-                TokenType::RegexLiteral => Expression::RegexLiteral(token.clone()),
-                TokenType::StringLiteral => Expression::StringLiteral(token.clone()),
-                TokenType::Keyword => Expression::Keyword(token.clone()),
-
-                // Unary operators
-                TokenType::RepeatOne => Expression::RepeatOne(Box::new(operands.pop().unwrap())),
-                TokenType::RepeatZero => Expression::RepeatZero(Box::new(operands.pop().unwrap())),
-                TokenType::Optional => Expression::Optional(Box::new(operands.pop().unwrap())),
-                TokenType::Discard => Expression::Discard(Box::new(operands.pop().unwrap())),
-                TokenType::Meta => Expression::Meta(Box::new(operands.pop().unwrap())),
-
-                // Binary operators
-                TokenType::And => {
-                    let right = operands.pop().unwrap();
-                    let left = operands.pop().unwrap();
-                    Expression::And(Box::new(left), Box::new(right))
-                }
-                TokenType::Or => {
-                    let right = operands.pop().unwrap();
-                    let left = operands.pop().unwrap();
-                    Expression::Or(Box::new(left), Box::new(right))
-                }
-                TokenType::DelimitRepeat => {
-                    if postfix[i + 1].token_type == TokenType::RepeatOne {
-                        i += 1; // Skip the RepeatOne token
-                        let right = operands.pop().unwrap();
-                        let left = operands.pop().unwrap();
-                        Expression::DelimitRepeatOne(Box::new(left), Box::new(right))
-                    } else if postfix[i + 1].token_type == TokenType::RepeatZero {
-                        i += 1; // Skip the RepeatZero token
-                        let right = operands.pop().unwrap();
-                        let left = operands.pop().unwrap();
-                        Expression::DelimitRepeatZero(Box::new(left), Box::new(right))
-                    } else {
-                        return Err(format!(
-                            "Expected RepeatOne or RepeatZero after DelimitRepeat at position {}",
-                            token.position
-                        )
-                        .into());
-                    }
-                }
-
-                _ => {
-                    return Err(format!(
-                        "Unexpected token {:?} at position {}",
-                        token.token_type, token.position
-                    )
-                    .into());
-                }
-            };
-
-            operands.push(expression);
-
-            i += 1;
-        }
-
-        Ok(operands.pop().unwrap())
-    }
-
-    fn split_into_alternatives(&self, expression: &Expression) -> Vec<Expression> {
-        match expression {
-            Expression::Or(left, right) => {
-                let mut alternatives = self.split_into_alternatives(left);
-                alternatives.extend(self.split_into_alternatives(right));
-                alternatives
-            }
-            _ => vec![expression.clone()],
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum StructureType {
-    ConfigDirective,
-    RuleDefinition,
-    MetaRuleDefinition,
-    DiscardRuleDefinition,
-}
-
-#[derive(Debug, Clone)]
-pub struct Structure {
-    tokens: Vec<Token>,
-    structure_type: StructureType,
-}
-
-impl Structure {
-    pub fn new(tokens: Vec<Token>, structure_type: StructureType) -> Self {
-        Structure {
-            tokens,
-            structure_type,
-        }
-    }
-}
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::gramspec_parser::diagnostic::{Diagnostic, Diagnostics};
+use crate::gramspec_parser::gramspec;
+use crate::gramspec_parser::token;
+use gramspec::{END_OF_INPUT_KEYWORD, GramSpec, expression::Expression};
+use gramspec::precedence::{Associativity, PrecedenceLevel};
+use token::{Token, token_type::TokenType};
+
+use regex::escape;
+
+mod self_hosted;
+mod tokenizer;
+
+/// Adds a rule of one kind, such as a meta rule, to a grammar specification.
+type AddRule = fn(&mut GramSpec, String, Vec<Expression>);
+
+pub struct Parser {
+    tokenizer: tokenizer::Tokenizer,
+    structures: Vec<Structure>,
+    tokens: Vec<Token>,
+    position: usize,
+    file: Option<Arc<PathBuf>>,
+    /// Canonical paths of the files currently being included, used to detect include cycles.
+    include_stack: Vec<PathBuf>,
+    /// Canonical paths of every file read so far, so that each file is only included once.
+    included: HashSet<PathBuf>,
+    /// Errors found so far, reported together at the end of the parse.
+    diagnostics: Vec<Diagnostic>,
+    /// Whether the grammar is parsed by the parser generated from `grammars/gramspec.grm` instead
+    /// of the hand-written one.
+    self_hosted: bool,
+}
+
+impl Parser {
+    /// Creates a new Parser with the given tokens.
+    pub fn new(input: String) -> Self {
+        Parser {
+            tokenizer: tokenizer::Tokenizer::new(input),
+            structures: Vec::new(),
+            tokens: Vec::new(),
+            position: 0,
+            file: None,
+            include_stack: Vec::new(),
+            included: HashSet::new(),
+            diagnostics: Vec::new(),
+            self_hosted: false,
+        }
+    }
+
+    /// Parses the grammar, and any files it includes, with the parser generated from
+    /// `grammars/gramspec.grm`, the grammar of the grammar language itself, instead of the
+    /// hand-written tokenizer and parser. The hand-written parser stays the default until the
+    /// generated one reports errors as precisely and parses as fast.
+    pub fn self_hosted(mut self) -> Self {
+        self.self_hosted = true;
+        self
+    }
+
+    /// Creates a new Parser for the given grammar file. Files included by the grammar are
+    /// resolved relative to it.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path)?;
+        let canonical = fs::canonicalize(path)?;
+
+        let mut parser = Parser::new(input);
+        parser.file = Some(Arc::new(path.to_path_buf()));
+        parser.include_stack.push(canonical.clone());
+        parser.included.insert(canonical);
+        Ok(parser)
+    }
+
+    /// Parses the grammar specification. A definition with an error is skipped, and every error
+    /// found in the grammar is returned together as `Diagnostics`.
+    pub fn parse(&mut self) -> Result<GramSpec, Box<dyn Error>> {
+        if self.self_hosted {
+            return self.parse_self_hosted();
+        }
+        self.parse_hand_written()
+    }
+
+    /// Parses the grammar specification with the hand-written tokenizer and parser. A line with an
+    /// error is skipped, and every error found in the grammar is returned together as
+    /// `Diagnostics`.
+    fn parse_hand_written(&mut self) -> Result<GramSpec, Box<dyn Error>> {
+        let (tokens, diagnostics) = self.tokenizer.tokenize();
+        self.tokens = tokens;
+        if let Some(file) = &self.file {
+            for token in &mut self.tokens {
+                token.file = Some(file.clone());
+            }
+        }
+        for diagnostic in diagnostics {
+            self.report(diagnostic);
+        }
+        self.structures = self.structurize();
+        let mut rules: Vec<Structure> = Vec::new();
+        let mut config_directives: Vec<Structure> = Vec::new();
+        let mut meta_rules: Vec<Structure> = Vec::new();
+        let mut discard_rules: Vec<Structure> = Vec::new();
+        let mut keywords: Vec<Structure> = Vec::new();
+        let mut precedence_rules: Vec<Structure> = Vec::new();
+        let mut recoveries: Vec<Structure> = Vec::new();
+
+        for structure in &self.structures {
+            match structure.structure_type {
+                StructureType::RuleDefinition => rules.push(structure.clone()),
+                StructureType::ConfigDirective => config_directives.push(structure.clone()),
+                StructureType::MetaRuleDefinition => meta_rules.push(structure.clone()),
+                StructureType::DiscardRuleDefinition => discard_rules.push(structure.clone()),
+                StructureType::KeywordDefinition => keywords.push(structure.clone()),
+                StructureType::PrecedenceRuleDefinition => precedence_rules.push(structure.clone()),
+                StructureType::RecoveryDefinition => recoveries.push(structure.clone()),
+            }
+        }
+
+        let mut gramspec = self.new_gramspec();
+
+        for rule in &rules {
+            self.add_rule(&mut gramspec, &rule.tokens[0], |parser| parser.to_alternatives(&rule.tokens[0], &rule.tokens[1..]), GramSpec::add_rule);
+        }
+
+        for meta_rule in &meta_rules {
+            self.add_rule(&mut gramspec, &meta_rule.tokens[0], |parser| parser.to_alternatives(&meta_rule.tokens[0], &meta_rule.tokens[1..]), GramSpec::add_meta_rule);
+        }
+
+        for discard_rule in &discard_rules {
+            self.add_rule(&mut gramspec, &discard_rule.tokens[0], |parser| parser.to_alternatives(&discard_rule.tokens[0], &discard_rule.tokens[1..]), GramSpec::add_discard_rule);
+        }
+
+        for precedence_rule in &precedence_rules {
+            self.add_precedence_rule(&mut gramspec, &precedence_rule.tokens[0], |parser| parser.to_precedence_table(precedence_rule));
+        }
+
+        for recovery in &recoveries {
+            let name = &recovery.tokens[0];
+            self.add_recovery(&mut gramspec, name, |parser| {
+                if recovery.tokens.len() == 1 {
+                    Err(Diagnostic::error(format!("Recovery for '{}' has no synchronisation tokens", name.value), name).into())
+                } else {
+                    parser.to_alternatives(name, &recovery.tokens[1..])
+                }
+            });
+        }
+
+        for keyword in &keywords {
+            self.add_keyword(&mut gramspec, &keyword.tokens[0], &keyword.tokens[1]);
+        }
+
+        for config_directive in &config_directives {
+            let result = self.apply_config_directive(&mut gramspec, &config_directive.tokens[0], &config_directive.tokens[1]);
+            self.check(result);
+        }
+
+        self.finish(gramspec)
+    }
+
+    /// Returns an empty grammar specification read from the file of this parser.
+    fn new_gramspec(&self) -> GramSpec {
+        let mut gramspec = GramSpec::new();
+        if let Some(file) = &self.file {
+            gramspec.sources.push(file.to_path_buf());
+        }
+        gramspec.texts.push((self.file.clone(), self.tokenizer.input().to_string()));
+        gramspec
+    }
+
+    /// Defines a rule and adds its alternatives with `add`, reporting any error.
+    fn add_rule(
+        &mut self,
+        gramspec: &mut GramSpec,
+        name: &Token,
+        alternatives: impl FnOnce(&Self) -> Result<Vec<Expression>, Box<Diagnostic>>,
+        add: AddRule,
+    ) {
+        let result = gramspec.define(name).and_then(|_| alternatives(self));
+        if let Some(alternatives) = self.check(result) {
+            add(gramspec, name.value.clone(), alternatives);
+        }
+    }
+
+    /// Defines a precedence rule and adds its operand and levels, reporting any error.
+    fn add_precedence_rule(
+        &mut self,
+        gramspec: &mut GramSpec,
+        name: &Token,
+        table: impl FnOnce(&Self) -> Result<(Vec<Expression>, Vec<PrecedenceLevel>), Box<Diagnostic>>,
+    ) {
+        let result = gramspec.define(name).and_then(|_| table(self));
+        if let Some((operand, levels)) = self.check(result) {
+            gramspec.add_precedence_rule(name.value.clone(), operand, levels);
+        }
+    }
+
+    /// Adds the synchronisation expressions of a rule, reporting any error.
+    fn add_recovery(
+        &mut self,
+        gramspec: &mut GramSpec,
+        name: &Token,
+        alternatives: impl FnOnce(&Self) -> Result<Vec<Expression>, Box<Diagnostic>>,
+    ) {
+        let result = if gramspec.recovery.contains_key(&name.value) {
+            Err(Diagnostic::error(format!("Recovery for '{}' is declared twice", name.value), name).into())
+        } else {
+            alternatives(self)
+        };
+        if let Some(alternatives) = self.check(result) {
+            gramspec.add_recovery(name.value.clone(), alternatives);
+            gramspec.recovery_definitions.insert(name.value.clone(), name.clone());
+        }
+    }
+
+    /// Defines a keyword matching the text of a string literal, reporting any error.
+    fn add_keyword(&mut self, gramspec: &mut GramSpec, name: &Token, value: &Token) {
+        let result = if name.value == END_OF_INPUT_KEYWORD {
+            Err(Diagnostic::error(format!("'{}' cannot be redefined", END_OF_INPUT_KEYWORD), name)
+                .with_note("it is reserved for the end of the input")
+                .into())
+        } else {
+            gramspec.define(name)
+        };
+        if self.check(result).is_some() {
+            gramspec.add_keyword(name.value.clone(), value.value.clone());
+        }
+    }
+
+    /// Removes the rules used as ignore_between patterns and returns the grammar specification,
+    /// or every error found in it.
+    fn finish(&mut self, mut gramspec: GramSpec) -> Result<GramSpec, Box<dyn Error>> {
+        // Loop through ignore_between rules and remove them from the normal rule lists
+        for ignore_rule in &gramspec.config.ignore_between {
+            gramspec.rules.remove(ignore_rule);
+            gramspec.meta_rules.remove(ignore_rule);
+            gramspec.discard_rules.remove(ignore_rule);
+            gramspec.precedence.remove(ignore_rule);
+        }
+
+        let mut diagnostics = Diagnostics { diagnostics: std::mem::take(&mut self.diagnostics) };
+        if diagnostics.error_count() > 0 {
+            diagnostics.diagnostics.sort_by_key(|diagnostic| (diagnostic.file.clone(), diagnostic.start));
+            return Err(Box::new(diagnostics));
+        }
+
+        Ok(gramspec)
+    }
+
+    /// Applies a config directive to the grammar specification, including the grammar file it
+    /// names if it is an include directive.
+    fn apply_config_directive(&mut self, gramspec: &mut GramSpec, name_token: &Token, value_token: &Token) -> Result<(), Box<Diagnostic>> {
+        let directive_name = &name_token.value;
+        if matches!(directive_name.as_str(), "include" | "import") {
+            if let Some(included) = self.include(value_token)? {
+                gramspec.merge(included)?;
+            }
+            return Ok(());
+        }
+        let directive_value = {
+            if value_token.token_type == TokenType::RegexLiteral {
+                value_token.value.clone()
+            } else {
+                // Escapes the string literal value so that quotes and backslashes are handled correctly
+                escape(&value_token.value)
+            }
+        };
+
+        // Only ignore_between can be given more than once
+        if directive_name != "ignore_between"
+            && let Some(previous) = gramspec.config.definitions.get(directive_name).and_then(|tokens| tokens.first())
+        {
+            return Err(Diagnostic::error(format!("'{}' is set twice", directive_name), value_token)
+                .with_note(format!("it is first set at {}", previous.location()))
+                .into());
+        }
+
+        gramspec
+            .config
+            .set(directive_name.clone(), directive_value)
+            .map_err(|err| {
+                Diagnostic::error(err.to_string(), name_token)
+                    .with_help("the options are entry_rule, ignore_between and include")
+            })?;
+        gramspec.config.definitions.entry(directive_name.clone()).or_default().push(value_token.clone());
+        Ok(())
+    }
+
+    /// Parses the phrase of a rule into its alternatives.
+    fn to_alternatives(&self, name: &Token, phrase: &[Token]) -> Result<Vec<Expression>, Box<Diagnostic>> {
+        if phrase.is_empty() {
+            return Err(Diagnostic::error(format!("'{}' has an empty definition", name.value), name).into());
+        }
+        let and_phrase = self.add_implict_ands(phrase);
+        let expression = self.to_expression(and_phrase)?;
+        Ok(self.split_into_alternatives(&expression))
+    }
+
+    /// Parses the grammar file named by an include directive, resolved relative to the including
+    /// file. Returns None if the file was already included, or if it has errors, which are
+    /// reported along with the errors of the including file.
+    fn include(&mut self, path_token: &Token) -> Result<Option<GramSpec>, Box<Diagnostic>> {
+        if path_token.token_type != TokenType::StringLiteral {
+            return Err(self.unexpected_at("a quoted file name to include", path_token).into());
+        }
+
+        let directory = self.file.as_deref().and_then(|file| file.parent()).unwrap_or(Path::new(""));
+        let path = directory.join(&path_token.value);
+        let canonical = fs::canonicalize(&path).map_err(|err| {
+            Diagnostic::error(format!("Could not include '{}': {}", path.display(), err), path_token)
+        })?;
+
+        if self.include_stack.contains(&canonical) {
+            let cycle = self
+                .include_stack
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(Diagnostic::error("Include cycle", path_token).with_note(cycle).into());
+        }
+        if self.included.contains(&canonical) {
+            return Ok(None);
+        }
+
+        let mut parser = Parser::from_file(&path).map_err(|err| {
+            Diagnostic::error(format!("Could not include '{}': {}", path.display(), err), path_token)
+        })?;
+        parser.self_hosted = self.self_hosted;
+        parser.include_stack = self.include_stack.clone();
+        parser.include_stack.push(canonical);
+        parser.included.extend(self.included.drain());
+        let result = parser.parse();
+        self.included = parser.included;
+        match result {
+            Ok(gramspec) => Ok(Some(gramspec)),
+            Err(err) => match err.downcast::<Diagnostics>() {
+                Ok(diagnostics) => {
+                    self.diagnostics.extend(diagnostics.diagnostics);
+                    Ok(None)
+                }
+                Err(err) => Err(Diagnostic::error(format!("Could not include '{}': {}", path.display(), err), path_token).into()),
+            },
+        }
+    }
+
+    /// Splits the tokens into structures, one per logical line. A line with an error is reported
+    /// and skipped, so that the errors of every line are found in one run.
+    fn structurize(&mut self) -> Vec<Structure> {
+        let mut structures = Vec::new();
+        while self.position < self.tokens.len() {
+            let initial_pos = self.position;
+            match self.expect_structure() {
+                Ok(structure) => structures.push(structure),
+                Err(diagnostic) => {
+                    self.report(*diagnostic);
+                    self.position = initial_pos;
+                    self.skip_line();
+                }
+            }
+        }
+        structures
+    }
+
+    /// Reads the structure at the current position, keeping the longest one that matches.
+    fn expect_structure(&mut self) -> Result<Structure, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::RuleDefinition);
+        let initial_pos = self.position;
+        let mut longest_pos = 0;
+
+        // Try to parse a config directive
+        if let Some(new_structure) = self.expect_config_directive()?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse a rule definition
+        if let Some(new_structure) = self.expect_rule_definition()?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse a meta rule definition
+        if let Some(new_structure) = self.expect_meta_rule_definition()?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse a discard rule definition
+        if let Some(new_structure) = self.expect_discard_rule_definition()?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse a precedence rule definition
+        if let Some(new_structure) = self.expect_rule_directive("precedence", StructureType::PrecedenceRuleDefinition)?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse the recovery tokens of a rule
+        if let Some(new_structure) = self.expect_rule_directive("recover", StructureType::RecoveryDefinition)?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+
+        // Reset position
+        self.position = initial_pos;
+        // Try to parse a keyword definition
+        if let Some(new_structure) = self.expect_keyword_definition()?
+            && self.position > longest_pos
+        {
+            structure = new_structure;
+            longest_pos = self.position;
+        }
+
+        if longest_pos == 0 {
+            self.position = initial_pos;
+            return Err(self.unexpected("a rule, keyword or directive").into());
+        }
+
+        // Set position to the end of the structure
+        self.position = longest_pos;
+
+        Ok(structure)
+    }
+
+    /// Moves past the end of the current logical line.
+    fn skip_line(&mut self) {
+        while self.position < self.tokens.len() {
+            self.position += 1;
+            if self.tokens[self.position - 1].token_type == TokenType::Newline {
+                break;
+            }
+        }
+    }
+
+    /// Returns an error for the current token, which is not the expected one.
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        self.unexpected_at(expected, &self.tokens[self.position])
+    }
+
+    /// Returns an error for a token that is not the expected one.
+    fn unexpected_at(&self, expected: &str, token: &Token) -> Diagnostic {
+        let found = match token.token_type {
+            TokenType::Newline => "the end of the line".to_string(),
+            _ => format!("'{}'", token.value),
+        };
+        Diagnostic::error(format!("Expected {}, found {}", expected, found), token)
+    }
+
+    /// Records a diagnostic, attaching the grammar line it points at.
+    fn report(&mut self, mut diagnostic: Diagnostic) {
+        if diagnostic.file.is_none() {
+            diagnostic.file = self.file.clone();
+        }
+        if diagnostic.source_line.is_none() && diagnostic.file == self.file {
+            diagnostic.source_line = self.tokenizer.line_text(diagnostic.line).map(str::to_string);
+            diagnostic.column_utf16 = self.tokenizer.get_column_utf16(diagnostic.start);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Records the error of a result, if any, and returns its value otherwise.
+    fn check<T>(&mut self, result: Result<T, Box<Diagnostic>>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(diagnostic) => {
+                self.report(*diagnostic);
+                None
+            }
+        }
+    }
+
+    fn expect_config_directive(&mut self) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::ConfigDirective);
+
+        // Read the config directive token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::ConfigDirective {
+            self.position += 1;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the config directive name token. Directives that apply to a rule are read separately.
+        if self.tokens[self.position].token_type == TokenType::RuleName {
+            if matches!(self.tokens[self.position].value.as_str(), "precedence" | "recover") {
+                return Ok(None);
+            }
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("a config directive name").into());
+        }
+
+        // Read colon token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Read the config directive value token
+        match self.tokens[self.position].token_type {
+            TokenType::StringLiteral | TokenType::RegexLiteral => {
+                structure.tokens.push(self.tokens[self.position].clone());
+                self.position += 1;
+            }
+            _ => {
+                return Err(self.unexpected("a string or regex value").into());
+            }
+        }
+
+        // Read endline token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::Newline {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("the end of the line").into());
+        }
+
+        Ok(Some(structure))
+    }
+
+    fn expect_rule_definition(&mut self) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::RuleDefinition);
+
+        // Read the rule name token
+        if self.tokens[self.position].token_type == TokenType::RuleName {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the rule definition token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Skip a leading '|', which lets every alternative start on its own line
+        if self.tokens[self.position].token_type == TokenType::Or {
+            self.position += 1;
+        }
+
+        // Read tokens until we reach a newline or end of input
+        while self.position < self.tokens.len() {
+            if self.tokens[self.position].token_type == TokenType::Newline {
+                self.position += 1;
+                break;
+            }
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        }
+
+        Ok(Some(structure))
+    }
+
+    fn expect_meta_rule_definition(&mut self) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::MetaRuleDefinition);
+
+        // Read the meta rule token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::Meta {
+            self.position += 1;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the meta rule name token
+        if self.tokens[self.position].token_type == TokenType::RuleName {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("a meta rule name").into());
+        }
+
+        // Read the rule definition token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Skip a leading '|', which lets every alternative start on its own line
+        if self.tokens[self.position].token_type == TokenType::Or {
+            self.position += 1;
+        }
+
+        // Read tokens until we reach a newline or end of input
+        while self.position < self.tokens.len() {
+            if self.tokens[self.position].token_type == TokenType::Newline {
+                self.position += 1;
+                break;
+            }
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        }
+
+        Ok(Some(structure))
+    }
+
+    fn expect_discard_rule_definition(&mut self) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::DiscardRuleDefinition);
+
+        // Read the discard rule token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::Discard {
+            self.position += 1;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the discard rule name token
+        if self.tokens[self.position].token_type == TokenType::RuleName {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("a discard rule name").into());
+        }
+
+        // Read the rule definition token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Skip a leading '|', which lets every alternative start on its own line
+        if self.tokens[self.position].token_type == TokenType::Or {
+            self.position += 1;
+        }
+
+        // Read tokens until we reach a newline or end of input
+        while self.position < self.tokens.len() {
+            if self.tokens[self.position].token_type == TokenType::Newline {
+                self.position += 1;
+                break;
+            }
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        }
+
+        Ok(Some(structure))
+    }
+
+    /// Reads a directive that applies to a rule, such as `@precedence expr: ...`, keeping the rule
+    /// name and the tokens of its body.
+    fn expect_rule_directive(&mut self, directive: &str, structure_type: StructureType) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), structure_type);
+
+        // Read the directive tokens, don't bother adding them to the structure
+        if self.tokens[self.position].token_type == TokenType::ConfigDirective
+            && self.tokens[self.position + 1].token_type == TokenType::RuleName
+            && self.tokens[self.position + 1].value == directive
+        {
+            self.position += 2;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the rule name token
+        if self.tokens[self.position].token_type == TokenType::RuleName {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Err(self.unexpected(&format!("a rule name after '@{}'", directive)).into());
+        }
+
+        // Read the rule definition token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Skip a leading '|', which lets every alternative start on its own line
+        if self.tokens[self.position].token_type == TokenType::Or {
+            self.position += 1;
+        }
+
+        // Read tokens until we reach a newline or end of input
+        while self.position < self.tokens.len() {
+            if self.tokens[self.position].token_type == TokenType::Newline {
+                self.position += 1;
+                break;
+            }
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        }
+
+        Ok(Some(structure))
+    }
+
+    fn expect_keyword_definition(&mut self) -> Result<Option<Structure>, Box<Diagnostic>> {
+        let mut structure = Structure::new(Vec::new(), StructureType::KeywordDefinition);
+
+        // Read the keyword name token
+        if self.tokens[self.position].token_type == TokenType::Keyword {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Ok(None);
+        }
+
+        // Read the rule definition token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::RuleDefinition {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("':'").into());
+        }
+
+        // Read the keyword value token
+        if self.tokens[self.position].token_type == TokenType::StringLiteral {
+            structure.tokens.push(self.tokens[self.position].clone());
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("a string value for the keyword").into());
+        }
+
+        // Read endline token, don't bother adding it to the structure
+        if self.tokens[self.position].token_type == TokenType::Newline {
+            self.position += 1;
+        } else {
+            return Err(self.unexpected("the end of the line").into());
+        }
+
+        Ok(Some(structure))
+    }
+
+    /// Splits the body of a precedence rule into its operand alternatives and its levels. Each
+    /// level starts with its associativity followed by a ':', as in `left: '+' '-'`.
+    fn to_precedence_table(&self, structure: &Structure) -> Result<(Vec<Expression>, Vec<PrecedenceLevel>), Box<Diagnostic>> {
+        let body = &structure.tokens[1..];
+        let starts_level = |i: usize| {
+            body[i].token_type == TokenType::RuleName
+                && body.get(i + 1).is_some_and(|next| next.token_type == TokenType::RuleDefinition)
+        };
+
+        let operand_end = (0..body.len()).find(|&i| starts_level(i)).unwrap_or(body.len());
+        if operand_end == 0 {
+            let name = &structure.tokens[0];
+            return Err(Diagnostic::error(format!("Precedence rule '{}' has no operand", name.value), name).into());
+        }
+        let operand = self.to_alternatives(&structure.tokens[0], &body[..operand_end])?;
+
+        let mut levels: Vec<PrecedenceLevel> = Vec::new();
+        let mut i = operand_end;
+        while i < body.len() {
+            if starts_level(i) {
+                let associativity = Associativity::from_token(&body[i])?;
+                levels.push(PrecedenceLevel { associativity, operators: Vec::new() });
+                i += 2;
+                continue;
+            }
+
+            let token = &body[i];
+            let operator = match token.token_type {
+                // Operators may be separated by '|' for readability
+                TokenType::Or => {
+                    i += 1;
+                    continue;
+                }
+                TokenType::StringLiteral => Expression::StringLiteral(token.clone()),
+                TokenType::RegexLiteral => Expression::RegexLiteral(token.clone()),
+                TokenType::Keyword if token.value != END_OF_INPUT_KEYWORD => Expression::Keyword(token.clone()),
+                _ => {
+                    return Err(self.unexpected_at("an operator string, regex or keyword", token).into());
+                }
+            };
+            levels.last_mut().unwrap().operators.push(operator);
+            i += 1;
+        }
+
+        if let Some(level) = levels.iter().position(|level| level.operators.is_empty()) {
+            let name = &structure.tokens[0];
+            return Err(Diagnostic::error(
+                format!("Level {} of precedence rule '{}' has no operators", level + 1, name.value),
+                name,
+            )
+            .into());
+        }
+
+        Ok((operand, levels))
+    }
+
+    fn add_implict_ands(&self, tokens: &[Token]) -> Vec<Token> {
+        let mut final_ = Vec::new();
+        if tokens.is_empty() {
+            return final_;
+        }
+        for i in 0..(tokens.len() - 1) {
+            let token = &tokens[i];
+            let next_token = &tokens[i + 1];
+
+            final_.push(token.clone());
+
+            if token.token_type == TokenType::OpenParen {
+                continue;
+            }
+            if token.token_type == TokenType::DelimitRepeat {
+                continue;
+            }
+            if token.token_type == TokenType::Or {
+                continue;
+            }
+            if token.token_type == TokenType::And {
+                continue;
+            }
+            if token.token_type.is_prefix_operator() {
+                continue;
+            }
+
+            if next_token.token_type == TokenType::CloseParen {
+                continue;
+            }
+            if next_token.token_type.is_operator() && !next_token.token_type.is_prefix_operator() {
+                continue;
+            }
+
+            let (line, column) = self.tokenizer.get_line_column(token.position);
+
+            final_.push(Token {
+                value: String::from("&"),
+                token_type: TokenType::And,
+                position: token.position + 1,
+                line,
+                column: column + 1,
+                length: 0,
+                file: token.file.clone(),
+            });
+        }
+
+        final_.push(tokens[tokens.len() - 1].clone());
+
+        final_
+    }
+
+    /// Checks that every operator of an expression has its operands and that its parentheses are
+    /// balanced, so that the expression can be built from it.
+    fn check_operands(&self, tokens: &[Token]) -> Result<(), Box<Diagnostic>> {
+        let mut open_parens: Vec<&Token> = Vec::new();
+        let mut expect_operand = true;
+        let mut previous: Option<&Token> = None;
+        for token in tokens {
+            let token_type = &token.token_type;
+            if !token_type.is_operand() && !token_type.is_operator() && !token_type.is_paren() {
+                let unexpected = Diagnostic::error(format!("Unexpected '{}'", token.value), token);
+                return Err(match token_type {
+                    TokenType::RuleDefinition => {
+                        unexpected.with_help("the line before may end with an operator that continues it onto this line")
+                    }
+                    _ => unexpected,
+                }
+                .into());
+            }
+            if expect_operand {
+                if token_type.is_operand() {
+                    expect_operand = false;
+                } else if *token_type == TokenType::OpenParen {
+                    open_parens.push(token);
+                } else if token_type.is_prefix_operator() {
+                    // The operand follows the operator
+                } else if let Some(previous) = previous.filter(|previous| previous.token_type != TokenType::OpenParen) {
+                    return Err(Self::missing_right_operand(previous).into());
+                } else if *token_type == TokenType::CloseParen {
+                    return Err(match previous {
+                        Some(open) => Diagnostic::error("Empty parentheses", open),
+                        None => Diagnostic::error("Unmatched ')'", token),
+                    }
+                    .into());
+                } else {
+                    let side = if token_type.is_binary_operator() { "a left-hand operand" } else { "an operand" };
+                    let message = format!("'{}' is missing {}", Self::operator_text(token), side);
+                    return Err(Diagnostic::error(message, token).into());
+                }
+            } else if token_type.is_binary_operator() {
+                expect_operand = true;
+            } else if *token_type == TokenType::CloseParen {
+                if open_parens.pop().is_none() {
+                    return Err(Diagnostic::error("Unmatched ')'", token).into());
+                }
+            } else if !token_type.is_unary_operator() || token_type.is_prefix_operator() {
+                return Err(Diagnostic::error(format!("Unexpected '{}'", token.value), token).into());
+            }
+            previous = Some(token);
+        }
+
+        if expect_operand && let Some(previous) = previous {
+            return Err(Self::missing_right_operand(previous).into());
+        }
+        if let Some(open) = open_parens.pop() {
+            return Err(Self::missing_right_operand(open).into());
+        }
+        Ok(())
+    }
+
+    /// Returns an error for an operator or parenthesis that is not followed by an operand.
+    fn missing_right_operand(token: &Token) -> Diagnostic {
+        let text = Self::operator_text(token);
+        match token.token_type {
+            TokenType::OpenParen => Diagnostic::error("Unmatched '('", token).with_help("add a ')' to close it"),
+            ref token_type if token_type.is_binary_operator() => {
+                Diagnostic::error(format!("'{}' is missing a right-hand operand", text), token)
+            }
+            _ => Diagnostic::error(format!("'{}' is missing an operand", text), token),
+        }
+    }
+
+    /// Returns an operator as it is written in the grammar.
+    fn operator_text(token: &Token) -> String {
+        match token.token_type {
+            TokenType::Label => format!("{}=", token.value),
+            _ => token.value.clone(),
+        }
+    }
+
+    fn to_expression(&self, tokens: Vec<Token>) -> Result<Expression, Box<Diagnostic>> {
+        self.check_operands(&tokens)?;
+
+        // Conversion from infix to postfix notation
+
+        let mut postfix: Vec<Token> = Vec::new();
+        let mut stack: Vec<Token> = Vec::new();
+
+        for token in tokens {
+            if token.token_type.is_unary_operator() && !token.token_type.is_prefix_operator() {
+                if !stack.is_empty()
+                    && stack.last().unwrap().token_type == TokenType::DelimitRepeat
+                    && (token.token_type == TokenType::RepeatOne
+                        || token.token_type == TokenType::RepeatZero
+                        || token.token_type == TokenType::RepeatRange)
+                {
+                    postfix.push(stack.pop().unwrap());
+                }
+                postfix.push(token);
+            } else if token.token_type.is_operator() {
+                if stack.is_empty() {
+                    stack.push(token);
+                } else {
+                    while let Some(top) = stack.last() {
+                        if top.token_type.is_operator() {
+                            let top_prec = top.token_type.get_precedence();
+                            let token_prec = token.token_type.get_precedence();
+                            let is_right_associative = token.token_type.is_prefix_operator();
+
+                            if (is_right_associative && top_prec > token_prec)
+                                || (!is_right_associative && top_prec >= token_prec)
+                            {
+                                postfix.push(stack.pop().unwrap());
+                            } else {
+                                break;
+                            }
+                        } else {
+                            break;
+                        }
+                    }
+                    stack.push(token);
+                }
+            } else if token.token_type == TokenType::OpenParen {
+                stack.push(token);
+            } else if token.token_type == TokenType::CloseParen {
+                while let Some(top) = stack.pop() {
+                    if top.token_type == TokenType::OpenParen {
+                        break;
+                    }
+                    postfix.push(top);
+                }
+            } else {
+                postfix.push(token);
+            }
+        }
+
+        while let Some(top) = stack.pop() {
+            if top.token_type == TokenType::OpenParen {
+                return Err(Diagnostic::error("Unmatched '('", &top).with_help("add a ')' to close it").into());
+            }
+            postfix.push(top);
+        }
+
+        // Conversion from postfix to expression
+
+        if postfix.len() == 1 {
+            let token = postfix[0].clone();
+            match token.token_type {
+                TokenType::RuleName => return Ok(Expression::RuleName(token)),
+                TokenType::RegexLiteral => return Ok(Expression::RegexLiteral(token)),
+                TokenType::StringLiteral => return Ok(Expression::StringLiteral(token)),
+                TokenType::Keyword => return Ok(Self::keyword_expression(token)),
+                TokenType::Cut => return Ok(Expression::Cut(token)),
+                _ => {}
+            }
+        }
+
+        let mut operands: Vec<Expression> = Vec::new();
+
+        let mut i = 0;
+        while i < postfix.len() {
+            let token = &postfix[i];
+            let expression = match token.token_type {
+                // Literal/Identifier tokens
+                TokenType::RuleName => Expression::RuleName(token.clone()),
+                TokenType::RegexLiteral => Expression::RegexLiteral(token.clone()),
+                TokenType::StringLiteral => Expression::StringLiteral(token.clone()),
+                TokenType::Keyword => Self::keyword_expression(token.clone()),
+                TokenType::Cut => Expression::Cut(token.clone()),
+
+                // Unary operators
+                TokenType::RepeatOne => Expression::RepeatOne(Self::pop_operand(&mut operands, token)?),
+                TokenType::RepeatZero => Expression::RepeatZero(Self::pop_operand(&mut operands, token)?),
+                TokenType::Optional => Expression::Optional(Self::pop_operand(&mut operands, token)?),
+                TokenType::RepeatRange => {
+                    let (min, max) = self.repeat_bounds(token)?;
+                    Expression::RepeatRange(Self::pop_operand(&mut operands, token)?, min, max)
+                }
+                TokenType::Discard => Expression::Discard(Self::pop_operand(&mut operands, token)?),
+                TokenType::Meta => Expression::Meta(Self::pop_operand(&mut operands, token)?),
+                TokenType::Lookahead => Expression::Lookahead(Self::pop_operand(&mut operands, token)?),
+                TokenType::NotLookahead => Expression::NotLookahead(Self::pop_operand(&mut operands, token)?),
+                TokenType::Label => Expression::Label(token.value.clone(), Self::pop_operand(&mut operands, token)?),
+
+                // Binary operators
+                TokenType::And => {
+                    let right = Self::pop_operand(&mut operands, token)?;
+                    let left = Self::pop_operand(&mut operands, token)?;
+                    Expression::And(left, right)
+                }
+                TokenType::Or => {
+                    let right = Self::pop_operand(&mut operands, token)?;
+                    let left = Self::pop_operand(&mut operands, token)?;
+                    Expression::Or(left, right)
+                }
+                TokenType::DelimitRepeat => {
+                    // The repetition that follows the delimiter applies to both operands
+                    let repetition = postfix.get(i + 1).map(|next| next.token_type.clone());
+                    let right = Self::pop_operand(&mut operands, token)?;
+                    let left = Self::pop_operand(&mut operands, token)?;
+                    i += 1; // Skip the repetition token
+                    match repetition {
+                        Some(TokenType::RepeatOne) => Expression::DelimitRepeatOne(left, right),
+                        Some(TokenType::RepeatZero) => Expression::DelimitRepeatZero(left, right),
+                        Some(TokenType::RepeatRange) => {
+                            let (min, max) = self.repeat_bounds(&postfix[i])?;
+                            Expression::DelimitRepeatRange(left, right, min, max)
+                        }
+                        _ => {
+                            return Err(Diagnostic::error("Expected '+', '*' or a repetition range after ','", token)
+                                .with_help("write a delimited repetition as `item, delimiter+` or `item, delimiter*`")
+                                .into());
+                        }
+                    }
+                }
+
+                _ => {
+                    return Err(Diagnostic::error(format!("Unexpected '{}'", token.value), token).into());
+                }
+            };
+
+            operands.push(expression);
+
+            i += 1;
+        }
+
+        match (operands.pop(), operands.is_empty()) {
+            (Some(expression), true) => Ok(expression),
+            _ => Err(Diagnostic::error("Expected a single expression", &postfix[postfix.len() - 1]).into()),
+        }
+    }
+
+    /// Takes the operand of an operator off the operand stack.
+    fn pop_operand(operands: &mut Vec<Expression>, operator: &Token) -> Result<Box<Expression>, Box<Diagnostic>> {
+        match operands.pop() {
+            Some(operand) => Ok(Box::new(operand)),
+            None => Err(Diagnostic::error(format!("'{}' is missing an operand", Self::operator_text(operator)), operator).into()),
+        }
+    }
+
+    /// Turns a keyword token into an expression, treating the end of input keyword specially.
+    fn keyword_expression(token: Token) -> Expression {
+        if token.value == END_OF_INPUT_KEYWORD {
+            Expression::EndOfInput(token)
+        } else {
+            Expression::Keyword(token)
+        }
+    }
+
+    /// Reads the bounds of a `{n}`, `{n,}` or `{n,m}` repetition. A missing maximum means the
+    /// repetition is unbounded.
+    fn repeat_bounds(&self, token: &Token) -> Result<(usize, Option<usize>), Box<Diagnostic>> {
+        let invalid = |err: std::num::ParseIntError| {
+            Diagnostic::error(format!("Invalid repetition '{}': {}", token.value, err), token)
+        };
+        let inner = &token.value[1..token.value.len() - 1];
+        let (min, max) = match inner.split_once(',') {
+            Some((min, max)) => (min.trim().parse::<usize>().map_err(invalid)?, match max.trim() {
+                "" => None,
+                max => Some(max.parse::<usize>().map_err(invalid)?),
+            }),
+            None => {
+                let count = inner.trim().parse::<usize>().map_err(invalid)?;
+                (count, Some(count))
+            }
+        };
+        if let Some(max) = max
+            && max < min
+        {
+            return Err(Diagnostic::error(
+                format!("Invalid repetition '{}': the maximum is smaller than the minimum", token.value),
+                token,
+            )
+            .into());
+        }
+        Ok((min, max))
+    }
+
+    fn split_into_alternatives(&self, expression: &Expression) -> Vec<Expression> {
+        match expression {
+            Expression::Or(left, right) => {
+                let mut alternatives = self.split_into_alternatives(left);
+                alternatives.extend(self.split_into_alternatives(right));
+                alternatives
+            }
+            _ => vec![expression.clone()],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StructureType {
+    ConfigDirective,
+    RuleDefinition,
+    MetaRuleDefinition,
+    DiscardRuleDefinition,
+    KeywordDefinition,
+    PrecedenceRuleDefinition,
+    RecoveryDefinition,
+}
+
+#[derive(Debug, Clone)]
+pub struct Structure {
+    tokens: Vec<Token>,
+    structure_type: StructureType,
+}
+
+impl Structure {
+    pub fn new(tokens: Vec<Token>, structure_type: StructureType) -> Self {
+        Structure {
+            tokens,
+            structure_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gramspec_parser::diagnostic::Diagnostics;
+
+    /// Parses the grammar with the given parser and returns its errors as `message at line:column`.
+    fn errors(mut parser: Parser) -> Vec<String> {
+        let Err(err) = parser.parse() else {
+            panic!("the grammar should not parse");
+        };
+        let diagnostics = err.downcast_ref::<Diagnostics>().expect("errors are returned as diagnostics");
+        diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| format!("{} at {}:{}", diagnostic.message, diagnostic.line, diagnostic.column))
+            .collect()
+    }
+
+    /// Puts a definition on the fourth line of a grammar, after a rule, a blank line and a comment.
+    fn grammar_with(definition: &str) -> String {
+        format!("b: 'b'\n\n# {}\n{}\n", definition, definition)
+    }
+
+    #[test]
+    fn malformed_expressions_are_reported_at_the_operator() {
+        let cases = [
+            ("a: |", "'a' has an empty definition at 4:1"),
+            ("a: ,", "',' is missing a left-hand operand at 4:4"),
+            ("a:", "'a' has an empty definition at 4:1"),
+            ("a: x ,", "',' is missing a right-hand operand at 4:6"),
+            ("a: (x", "Unmatched '(' at 4:4"),
+            ("a: x)", "Unmatched ')' at 4:5"),
+            ("a: x{2,1}", "Invalid repetition '{2,1}': the maximum is smaller than the minimum at 4:5"),
+            ("a: , x", "',' is missing a left-hand operand at 4:4"),
+            ("a: x | | y", "'|' is missing a right-hand operand at 4:6"),
+        ];
+        for (definition, expected) in cases {
+            let parser = Parser::new(grammar_with(definition));
+            assert_eq!(errors(parser), [expected], "{}", definition);
+        }
+    }
+
+    #[test]
+    fn malformed_expressions_are_reported_by_the_self_hosted_parser() {
+        let cases = [
+            ("a: |", "5:1"),
+            ("a: ,", "4:4"),
+            ("a:", "5:1"),
+            ("a: x ,", "5:1"),
+            ("a: (x", "5:1"),
+            ("a: x)", "4:5"),
+            ("a: x{2,1}", "4:5"),
+            ("a: , x", "4:4"),
+            ("a: x | | y", "4:8"),
+        ];
+        for (definition, location) in cases {
+            let errors = errors(Parser::new(grammar_with(definition)).self_hosted());
+            assert_eq!(errors.len(), 1, "{}: {:?}", definition, errors);
+            assert!(errors[0].ends_with(&format!(" at {}", location)), "{}: {:?}", definition, errors);
+        }
+        assert_eq!(
+            errors(Parser::new(grammar_with("a: x)")).self_hosted()),
+            ["Expected one of postfix_operator, delimited, newline, '&', item, '|' or end of input, found ')' at 4:5"]
+        );
+    }
+
+    #[test]
+    fn names_of_underscores_are_read_alike_by_both_parsers() {
+        let grammar = "start: _ __ _A _1A\n_: 'a'\n__: 'b'\n_A: 'c'\n_1A: 'd'\n";
+        for mut parser in [Parser::new(grammar.to_string()), Parser::new(grammar.to_string()).self_hosted()] {
+            let gramspec = parser.parse().unwrap();
+            let mut rules: Vec<&String> = gramspec.rules.keys().collect();
+            rules.sort();
+            assert_eq!(rules, ["_", "__", "start"]);
+            let mut keywords: Vec<&String> = gramspec.keywords.keys().collect();
+            keywords.sort();
+            assert_eq!(keywords, ["_1A", "_A"]);
+        }
+    }
+
+    #[test]
+    fn every_definition_with_an_error_is_reported() {
+        let grammar = "a: x)\nb: 'b'\nc: , y\nd: 'd'\n";
+        assert_eq!(
+            errors(Parser::new(grammar.to_string())),
+            ["Unmatched ')' at 1:5", "',' is missing a left-hand operand at 3:4"]
+        );
+        assert_eq!(
+            errors(Parser::new(grammar.to_string()).self_hosted()),
+            [
+                "Expected one of postfix_operator, delimited, newline, '&', item, '|' or end of input, found ')' at 1:5",
+                "Expected one of newline, '|' or alternatives, found ',' at 3:4",
+            ]
+        );
+    }
+}
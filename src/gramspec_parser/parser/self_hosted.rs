@@ -0,0 +1,323 @@
+//! Builds a grammar specification from the tree returned by the parser generated from
+//! `grammars/gramspec.grm`, the grammar of the grammar language written in itself.
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+mod gramspec_parser;
+
+use std::error::Error;
+
+use gramspec_parser::node::{Node, NodeType};
+use gramspec_parser::{GramspecParser, ParseErrorKind};
+
+use super::{AddRule, Parser};
+use crate::gramspec_parser::diagnostic::Diagnostic;
+use crate::gramspec_parser::gramspec::GramSpec;
+use crate::gramspec_parser::gramspec::expression::Expression;
+use crate::gramspec_parser::gramspec::precedence::{Associativity, PrecedenceLevel};
+use crate::gramspec_parser::token::{Token, token_type::TokenType};
+
+impl Parser {
+    /// Parses the grammar specification with the generated parser and builds it from the tree.
+    /// Definitions are added in the same order as the hand-written parser adds them, so that both
+    /// report the same errors for them.
+    ///
+    /// A definition with a syntax error is skipped up to the end of its line by the `@recover` rule
+    /// of the grammar, so that the rest of the input is still parsed and checked.
+    pub(super) fn parse_self_hosted(&mut self) -> Result<GramSpec, Box<dyn Error>> {
+        let input = self.tokenizer.input().to_string();
+        let mut gramspec = self.new_gramspec();
+        let tree = match GramspecParser::new().expect_rule_names().parse_with_recovery(input) {
+            Ok((tree, diagnostics)) => {
+                for diagnostic in diagnostics {
+                    let found = diagnostic.found.as_deref();
+                    let message = Self::syntax_error_message(&diagnostic.expected, found);
+                    let (start, end) = (diagnostic.start_position, diagnostic.start_position + found.map_or(0, str::len));
+                    self.report(Diagnostic::error_at(message, start, end, diagnostic.line, diagnostic.column));
+                }
+                tree
+            }
+            Err(err) => {
+                let ParseErrorKind::Syntax = err.kind else {
+                    return Err(err.message().into());
+                };
+                let message = Self::syntax_error_message(&err.expected, err.found.as_deref());
+                self.report(Diagnostic::error_at(message, err.span.start, err.span.end, err.line, err.column));
+                return self.finish(gramspec);
+            }
+        };
+        // Lines that could not be parsed are left in error nodes, and definitions parsed with a
+        // missing literal are skipped too
+        let definitions = |node_type: NodeType| {
+            tree.children.iter().filter(move |node| node.node_type == node_type && !Self::has_missing_node(node))
+        };
+
+        let rule_kinds: [(NodeType, AddRule); 3] = [
+            (NodeType::RuleDefinition, GramSpec::add_rule),
+            (NodeType::MetaRule, GramSpec::add_meta_rule),
+            (NodeType::DiscardRule, GramSpec::add_discard_rule),
+        ];
+        for (node_type, add) in rule_kinds {
+            for rule in definitions(node_type) {
+                let Some(([name, alternatives], _)) = self.check(self.split_children(rule)) else {
+                    continue;
+                };
+                if let Some(name) = self.check(self.token(name, TokenType::RuleName)) {
+                    self.add_rule(&mut gramspec, &name, |parser| parser.alternatives(alternatives), add);
+                }
+            }
+        }
+
+        for precedence_rule in definitions(NodeType::PrecedenceRule) {
+            let Some(([name], _)) = self.check(self.split_children(precedence_rule)) else {
+                continue;
+            };
+            if let Some(name) = self.check(self.token(name, TokenType::RuleName)) {
+                self.add_precedence_rule(&mut gramspec, &name, |parser| parser.precedence_table(precedence_rule));
+            }
+        }
+
+        for recovery in definitions(NodeType::Recovery) {
+            let Some(([name, alternatives], _)) = self.check(self.split_children(recovery)) else {
+                continue;
+            };
+            if let Some(name) = self.check(self.token(name, TokenType::RuleName)) {
+                self.add_recovery(&mut gramspec, &name, |parser| parser.alternatives(alternatives));
+            }
+        }
+
+        for keyword in definitions(NodeType::KeywordDefinition) {
+            let Some(([name, value], _)) = self.check(self.split_children(keyword)) else {
+                continue;
+            };
+            let name = self.token(name, TokenType::Keyword);
+            let value = self.token(value, TokenType::StringLiteral);
+            if let Some((name, value)) = self.check(name.and_then(|name| Ok((name, value?)))) {
+                self.add_keyword(&mut gramspec, &name, &value);
+            }
+        }
+
+        for config_directive in definitions(NodeType::ConfigDirective) {
+            let Some(([name, value], _)) = self.check(self.split_children(config_directive)) else {
+                continue;
+            };
+            let value_type = match value.node_type {
+                NodeType::RegexLiteral => TokenType::RegexLiteral,
+                _ => TokenType::StringLiteral,
+            };
+            let name = self.token(name, TokenType::RuleName);
+            let value = self.token(value, value_type);
+            if let Some((name, value)) = self.check(name.and_then(|name| Ok((name, value?)))) {
+                let result = self.apply_config_directive(&mut gramspec, &name, &value);
+                self.check(result);
+            }
+        }
+
+        self.finish(gramspec)
+    }
+
+    /// Splits the children of a node after the first `N`, which the rule of the node in
+    /// `grammars/gramspec.grm` always matches. A node with fewer children comes from a generated
+    /// parser that is out of date with this module, and is reported instead.
+    fn split_children<'a, const N: usize>(&self, node: &'a Node) -> Result<(&'a [Node; N], &'a [Node]), Box<Diagnostic>> {
+        node.children.split_first_chunk().ok_or_else(|| self.malformed(node))
+    }
+
+    fn malformed(&self, node: &Node) -> Box<Diagnostic> {
+        let (line, column) = self.tokenizer.get_line_column(node.start_position);
+        let message = format!("Unexpected {:?} node from the parser generated from grammars/gramspec.grm", node.node_type);
+        Diagnostic::error_at(message, node.start_position, node.end_position, line, column).into()
+    }
+
+    /// Whether the recovering parse inserted a missing literal anywhere in the node.
+    fn has_missing_node(node: &Node) -> bool {
+        node.node_type == NodeType::_Missing || node.children.iter().any(Self::has_missing_node)
+    }
+
+    /// Describes a syntax error as what was expected and what was found instead.
+    fn syntax_error_message(expected: &[String], found: Option<&str>) -> String {
+        let found = match found {
+            Some(found) if found.starts_with(['\r', '\n']) => "the end of the line".to_string(),
+            Some(found) => format!("'{}'", found.escape_debug()),
+            None => "the end of the file".to_string(),
+        };
+        let expected = match expected {
+            [] => return format!("Unexpected {}", found),
+            [single] => single.clone(),
+            [first, second] => format!("{} or {}", first, second),
+            [rest @ .., last] => format!("one of {} or {}", rest.join(", "), last),
+        };
+        format!("Expected {}, found {}", expected, found)
+    }
+
+    /// Returns the token matched by one of the token rules of the grammar, such as
+    /// `string_literal`, whose only child is the text it matched.
+    fn token(&self, node: &Node, token_type: TokenType) -> Result<Token, Box<Diagnostic>> {
+        let ([text], _) = self.split_children(node)?;
+        let raw = text.value.as_deref().unwrap_or_default();
+        let position = text.start_position;
+        let (line, column) = self.tokenizer.get_line_column(position);
+        let Some(value) = token_type.transform(raw) else {
+            let message = format!("Invalid escape sequence in {}", raw);
+            return Err(Diagnostic::error_at(message, position, position + raw.len(), line, column).into());
+        };
+        let mut token = Token::new(&token_type, &value, position, line, column);
+        token.length = raw.len();
+        token.file = self.file.clone();
+        Ok(token)
+    }
+
+    /// Reads the operand alternatives and the levels of a precedence rule.
+    fn precedence_table(&self, precedence_rule: &Node) -> Result<(Vec<Expression>, Vec<PrecedenceLevel>), Box<Diagnostic>> {
+        let ([_, operand], levels) = self.split_children(precedence_rule)?;
+        let operand = self.alternatives(operand)?;
+        let levels = levels
+            .iter()
+            .map(|level| self.precedence_level(level))
+            .collect::<Result<_, _>>()?;
+        Ok((operand, levels))
+    }
+
+    /// Reads a precedence level: its associativity followed by its operators.
+    fn precedence_level(&self, level: &Node) -> Result<PrecedenceLevel, Box<Diagnostic>> {
+        let ([associativity], operators) = self.split_children(level)?;
+        let associativity = Associativity::from_token(&self.token(associativity, TokenType::RuleName)?)?;
+        let operators = operators
+            .iter()
+            .map(|operator| match self.primary(operator)? {
+                Expression::EndOfInput(token) => {
+                    Err(self.unexpected_at("an operator string, regex or keyword", &token).into())
+                }
+                operator => Ok(operator),
+            })
+            .collect::<Result<_, Box<Diagnostic>>>()?;
+        Ok(PrecedenceLevel { associativity, operators })
+    }
+
+    /// Builds the alternatives of a rule from an `alternatives` node.
+    fn alternatives(&self, alternatives: &Node) -> Result<Vec<Expression>, Box<Diagnostic>> {
+        Ok(self.split_into_alternatives(&self.expression(alternatives)?))
+    }
+
+    /// Builds the expression of an `alternatives` or `group` node. Chains of `|` and of sequenced
+    /// items are grouped from the left, as `to_expression` groups them.
+    fn expression(&self, alternatives: &Node) -> Result<Expression, Box<Diagnostic>> {
+        let ([first], sequences) = self.split_children(alternatives)?;
+        let mut expression = self.sequence(first)?;
+        for sequence in sequences {
+            expression = Expression::Or(Box::new(expression), Box::new(self.sequence(sequence)?));
+        }
+        Ok(expression)
+    }
+
+    fn sequence(&self, sequence: &Node) -> Result<Expression, Box<Diagnostic>> {
+        let ([first], items) = self.split_children(sequence)?;
+        let mut expression = self.item(first)?;
+        for item in items {
+            expression = Expression::And(Box::new(expression), Box::new(self.item(item)?));
+        }
+        Ok(expression)
+    }
+
+    /// Builds an item: a unit followed by any number of delimited repetitions, such as
+    /// `arg, ','+`, each applying to everything before it.
+    fn item(&self, item: &Node) -> Result<Expression, Box<Diagnostic>> {
+        let ([unit], delimiteds) = self.split_children(item)?;
+        let mut expression = self.unit(unit)?;
+        for delimited in delimiteds {
+            // The delimiter is followed by any '?' operators, then the repetition
+            let ([delimiter], operators) = self.split_children(delimited)?;
+            let optional = operators.iter().take_while(|operator| operator.value.as_deref() == Some("?")).count();
+            let mut delimiter = self.primary(delimiter)?;
+            for operator in &operators[..optional] {
+                delimiter = self.postfix(operator, delimiter)?;
+            }
+
+            let (repetition, operators) = operators[optional..].split_first().ok_or_else(|| self.malformed(delimited))?;
+            let (left, right) = (Box::new(expression), Box::new(delimiter));
+            expression = match repetition.value.as_deref() {
+                Some("+") => Expression::DelimitRepeatOne(left, right),
+                Some("*") => Expression::DelimitRepeatZero(left, right),
+                _ => {
+                    let (min, max) = self.repeat_bounds(&self.token(repetition, TokenType::RepeatRange)?)?;
+                    Expression::DelimitRepeatRange(left, right, min, max)
+                }
+            };
+            for operator in operators {
+                expression = self.postfix(operator, expression)?;
+            }
+        }
+        Ok(expression)
+    }
+
+    /// Builds a unit: a primary expression with its prefix and postfix operators. The postfix
+    /// operators bind first, and the operators closest to the primary expression bind first.
+    fn unit(&self, unit: &Node) -> Result<Expression, Box<Diagnostic>> {
+        let is_prefix_operator = |node: &Node| {
+            node.node_type == NodeType::Label || matches!(node.value.as_deref(), Some("~" | "$" | ">" | "!"))
+        };
+        let primary = unit.children.iter().position(|node| !is_prefix_operator(node)).ok_or_else(|| self.malformed(unit))?;
+        let (prefix_operators, rest) = unit.children.split_at(primary);
+        let (primary, postfix_operators) = rest.split_first().ok_or_else(|| self.malformed(unit))?;
+
+        let mut expression = self.primary(primary)?;
+        for operator in postfix_operators {
+            expression = self.postfix(operator, expression)?;
+        }
+        for operator in prefix_operators.iter().rev() {
+            let operand = Box::new(expression);
+            expression = match operator.value.as_deref() {
+                Some("~") => Expression::Discard(operand),
+                Some("$") => Expression::Meta(operand),
+                Some(">") => Expression::Lookahead(operand),
+                Some("!") => Expression::NotLookahead(operand),
+                _ => Expression::Label(self.token(operator, TokenType::Label)?.value, operand),
+            };
+        }
+        Ok(expression)
+    }
+
+    /// Applies a postfix operator, either '+', '*', '?' or a repetition range.
+    fn postfix(&self, operator: &Node, operand: Expression) -> Result<Expression, Box<Diagnostic>> {
+        let operand = Box::new(operand);
+        Ok(match operator.value.as_deref() {
+            Some("+") => Expression::RepeatOne(operand),
+            Some("*") => Expression::RepeatZero(operand),
+            Some("?") => Expression::Optional(operand),
+            _ => {
+                let (min, max) = self.repeat_bounds(&self.token(operator, TokenType::RepeatRange)?)?;
+                Expression::RepeatRange(operand, min, max)
+            }
+        })
+    }
+
+    /// Builds a parenthesised expression or a literal, keyword or rule name.
+    fn primary(&self, primary: &Node) -> Result<Expression, Box<Diagnostic>> {
+        Ok(match primary.node_type {
+            NodeType::Group => self.expression(primary)?,
+            NodeType::RegexLiteral => Expression::RegexLiteral(self.token(primary, TokenType::RegexLiteral)?),
+            NodeType::StringLiteral => Expression::StringLiteral(self.token(primary, TokenType::StringLiteral)?),
+            NodeType::Keyword => Self::keyword_expression(self.token(primary, TokenType::Keyword)?),
+            NodeType::Cut => Expression::Cut(self.token(primary, TokenType::Cut)?),
+            _ => Expression::RuleName(self.token(primary, TokenType::RuleName)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_trees_are_reported_instead_of_panicking() {
+        let parser = Parser::new("a: b\n".to_string());
+        let group = |child| Node::new(NodeType::Group, vec![Node::new(child, vec![], None)], None);
+        for (child, name) in [(NodeType::Sequence, "Sequence"), (NodeType::StringLiteral, "StringLiteral")] {
+            let Err(diagnostic) = parser.primary(&group(child)) else {
+                panic!("a group of an empty {} should not be read", name);
+            };
+            let expected = format!("Unexpected {} node from the parser generated from grammars/gramspec.grm", name);
+            assert_eq!(diagnostic.message, expected);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::error::Error;
+use crate::gramspec_parser::diagnostic::Diagnostic;
 
 use super::token::token_type::TokenType;
 use super::token::Token;
@@ -6,118 +6,161 @@
 /// A tokenizer for the grammar specification.
 pub struct Tokenizer {
     input: String,
-    position: usize
+    /// Byte offset of the next character to tokenize.
+    position: usize,
+    /// Byte offset of the start of every line, used to find the line and column of a position.
+    line_starts: Vec<usize>,
 }
 
 impl Tokenizer {
 
     /// Creates a new Tokenizer with the given input string.
     pub fn new(input: String) -> Self {
-        Tokenizer { input, position: 0 }
-    }
-
-    /// Tokenizes the input string and returns a vector of tokens.
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, Box<dyn Error>> {
-        // Initialize a vector to hold the tokens
-        let mut tokens = Vec::new();
-        // Loop until we reach the end of the input string
-        while let Some(token) = self.next_token()? {
-            // Skip whitespace and comment tokens
-            if token.token_type == TokenType::Whitespace || token.token_type == TokenType::Comment {
-                // If the next token after this is a newline, skip this token
-                if let Some(next_token) = self.peek_token()? {
-                    // If the next token is a newline, skip this token
-                    if next_token.token_type == TokenType::Newline {
-                        self.next_token()?; // Consume the Newline token
+        // Lines end with "\r\n", "\n" or "\r", as newline tokens do
+        let bytes = input.as_bytes();
+        let line_ends =
+            (0..bytes.len()).filter(|&i| bytes[i] == b'\n' || (bytes[i] == b'\r' && bytes.get(i + 1) != Some(&b'\n')));
+        let line_starts = std::iter::once(0).chain(line_ends.map(|i| i + 1)).collect();
+        Tokenizer { input, position: 0, line_starts }
+    }
 
+    /// Tokenizes the input string and returns a vector of tokens, along with the characters that
+    /// could not be tokenized. A line with such a character is left out, so that it is not
+    /// reported again as an incomplete definition.
+    pub fn tokenize(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
+        // Read every token, including whitespace and comments
+        let mut raw_tokens = Vec::new();
+        let mut diagnostics = Vec::new();
+        loop {
+            match self.next_token() {
+                Ok(Some(token)) => raw_tokens.push(token),
+                Ok(None) => break,
+                Err(diagnostic) => {
+                    diagnostics.push(*diagnostic);
+                    while raw_tokens.last().is_some_and(|token: &Token| token.token_type != TokenType::Newline) {
+                        raw_tokens.pop();
                     }
+                    self.position = match self.input[self.position..].find('\n') {
+                        Some(offset) => self.position + offset,
+                        None => self.input.len(),
+                    };
                 }
-                // Continue to the next iteration of the loop
-                continue;
             }
-            // Add the token to the vector
-            tokens.push(token);
         }
-        // Return the vector of tokens
-        Ok(tokens)
+        // Drop whitespace and comments, and join continued lines
+        (self.join_lines(&raw_tokens), diagnostics)
     }
 
-    /// Gets the current line and column based on the current position.
-    pub fn get_line_column(&self, position: usize) -> (usize, usize) {
-        let mut line = 1;
-        let mut column = 1;
-        for (i, c) in self.input.chars().enumerate() {
-            if i >= position {
-                break;
-            }
-            if c == '\n' {
-                line += 1;
-                column = 1;
-            } else {
-                column += 1;
-            }
-        }
-        (line, column)
+    /// Returns the whole input being tokenized.
+    pub fn input(&self) -> &str {
+        &self.input
     }
 
-    /// Peeks at the next token without consuming it.
-    fn peek_token(&self) -> Result<Option<Token>, Box<dyn Error>> {
-        // Make sure we don't go out of bounds
-        if self.position >= self.input.len() {
-            return Ok(None);
-        }
+    /// Returns the text of a line of the input, without its line ending.
+    pub fn line_text(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.input.len(), |next| next - 1);
+        let text = &self.input[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
 
-        // Get slize of input from current position
-        let input_slice = &self.input[self.position..];
+    /// Removes whitespace and comment tokens and keeps only the newlines that end a logical line.
+    ///
+    /// A line is continued by the next one while a parenthesis is open, when the next line is
+    /// indented, when the line ends with a token that still expects an operand (such as `|` or
+    /// `:`), or when the next line starts with a binary operator.
+    fn join_lines(&self, raw_tokens: &[Token]) -> Vec<Token> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut paren_depth = 0usize;
 
-        // Initialize variables to track the longest match
-        let mut longest_length = 0;
-        let mut best_match: Option<(TokenType, String)> = None;
+        for (i, token) in raw_tokens.iter().enumerate() {
+            match token.token_type {
+                TokenType::Whitespace | TokenType::Comment => continue,
+                TokenType::Newline => {}
+                TokenType::OpenParen => {
+                    paren_depth += 1;
+                    tokens.push(token.clone());
+                    continue;
+                }
+                TokenType::CloseParen => {
+                    paren_depth = paren_depth.saturating_sub(1);
+                    tokens.push(token.clone());
+                    continue;
+                }
+                _ => {
+                    tokens.push(token.clone());
+                    continue;
+                }
+            }
 
-        // Iterate over all token types and find the longest match
-        for token_type in TokenType::all() {
-            // Get the regex match for the current token type
-            let regex = token_type.get_regex();
-            // If there is a match, check if it's the longest one
-            if let Some(result) = regex.captures(input_slice) {
-                let length = result[0].chars().count();
-                // If it is the longest match, update the variables
-                if length > longest_length {
-                    longest_length = length;
-                    best_match = Some((token_type, String::from(&result[0])));
+            // Find the first significant token after the newline, and whether its line is indented
+            let mut indented = false;
+            let mut line_start = true;
+            let mut next_token = None;
+            for next in &raw_tokens[i + 1..] {
+                match next.token_type {
+                    TokenType::Newline => {
+                        line_start = true;
+                        indented = false;
+                    }
+                    TokenType::Whitespace => {
+                        indented |= line_start;
+                        line_start = false;
+                    }
+                    TokenType::Comment => line_start = false,
+                    _ => {
+                        next_token = Some(next);
+                        break;
+                    }
                 }
             }
-        }
 
-        // Get the current line and column
-        let (line, column) = self.get_line_column(self.position);
+            let continues = match (tokens.last(), next_token) {
+                // Blank lines at the start of the input
+                (None, _) => true,
+                // The newline ends the last line of the input
+                (Some(_), None) => false,
+                (Some(last), Some(next)) => {
+                    paren_depth > 0
+                        || indented
+                        || last.token_type.expects_operand()
+                        || next.token_type.is_binary_operator()
+                }
+            };
 
-        // If no match was found, return an error
-        if longest_length == 0 {
-            let next_char = self.input[self.position..].chars().next().unwrap();
-            return Err(format!("Unexpected character '{}' at line {}, column {}", next_char, line, column).into());
+            if !continues && tokens.last().is_some_and(|last| last.token_type != TokenType::Newline) {
+                tokens.push(token.clone());
+            }
         }
 
-        // If a match was found, create a new token and update the position
-        if let Some((ltype, lmatch)) = best_match {
-            // Create the token
-            let token = Token::new(
-                &ltype,
-                &ltype.transform(&lmatch),
-                self.position,
-                line,
-                column
-            );
-            // Return the token
-            return Ok(Some(token));
+        // Make sure the last line is terminated, even without a trailing newline in the input
+        if tokens.last().is_some_and(|last| last.token_type != TokenType::Newline) {
+            let (line, column) = self.get_line_column(self.input.len());
+            tokens.push(Token::new(&TokenType::Newline, "\n", self.input.len(), line, column));
         }
 
-        // If we reach here, something went wrong
-        Ok(None)
+        tokens
+    }
+
+    /// Gets the line and column of a byte offset in the input. Columns count characters.
+    pub fn get_line_column(&self, position: usize) -> (usize, usize) {
+        let position = position.min(self.input.len());
+        let line = self.line_starts.partition_point(|&start| start <= position);
+        let line_start = self.line_starts[line - 1];
+        let column = self.input[line_start..position].chars().count() + 1;
+        (line, column)
+    }
+
+    /// Gets the column of a byte offset in the input in UTF-16 code units, counting from 1.
+    pub fn get_column_utf16(&self, position: usize) -> usize {
+        let position = position.min(self.input.len());
+        let line = self.line_starts.partition_point(|&start| start <= position);
+        let line_start = self.line_starts[line - 1];
+        self.input[line_start..position].encode_utf16().count() + 1
     }
 
     /// Gets the next token from the current position in the input string while consuming it.
-    fn next_token(&mut self) -> Result<Option<Token>, Box<dyn Error>> {
+    fn next_token(&mut self) -> Result<Option<Token>, Box<Diagnostic>> {
 
         // Make sure we don't go out of bounds
         if self.position >= self.input.len() {
@@ -131,17 +174,18 @@ fn next_token(&mut self) -> Result<Option<Token>, Box<dyn Error>> {
         let mut longest_length = 0;
         let mut best_match: Option<(TokenType, String)> = None;
 
-        // Iterate over all token types and find the longest match
-        for token_type in TokenType::all() {
+        // Find the longest match among the token types that can start with the next character
+        let next_char = input_slice.chars().next().unwrap();
+        for token_type in TokenType::starting_with(next_char) {
             // Get the regex match for the current token type
             let regex = token_type.get_regex();
             // If there is a match, check if it's the longest one
-            if let Some(result) = regex.captures(input_slice) {
-                let length = result[0].chars().count();
+            if let Some(result) = regex.find(input_slice) {
+                let length = result.len();
                 // If it is the longest match, update the variables
                 if length > longest_length {
                     longest_length = length;
-                    best_match = Some((token_type, String::from(&result[0])));
+                    best_match = Some((token_type.clone(), String::from(result.as_str())));
                 }
             }
         }
@@ -151,22 +195,28 @@ fn next_token(&mut self) -> Result<Option<Token>, Box<dyn Error>> {
 
         // If no match was found, return an error
         if longest_length == 0 {
-            let next_char = self.input[self.position..].chars().next().unwrap();
-            return Err(format!("Unexpected character '{}' at line {}, column {}", next_char, line, column).into());
+            let message = format!("Unexpected character '{}'", next_char);
+            let end = self.position + next_char.len_utf8();
+            return Err(Diagnostic::error_at(message, self.position, end, line, column).into());
         }
 
         // If a match was found, create a new token and update the position
         if let Some((ltype, lmatch)) = best_match {
             // Length of the match
-            let match_length = lmatch.chars().count();
+            let match_length = lmatch.len();
+            let Some(value) = ltype.transform(&lmatch) else {
+                let message = format!("Invalid escape sequence in {}", lmatch);
+                return Err(Diagnostic::error_at(message, self.position, self.position + match_length, line, column).into());
+            };
             // Create the token
-            let token = Token::new(
+            let mut token = Token::new(
                 &ltype,
-                &ltype.transform(&lmatch),
+                &value,
                 self.position,
                 line,
                 column
             );
+            token.length = match_length;
             // Update the position
             self.position += match_length;
             // Return the token
@@ -177,4 +227,125 @@ fn next_token(&mut self) -> Result<Option<Token>, Box<dyn Error>> {
         Ok(None)
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_token_type_that_can_match_is_tried_for_its_first_character() {
+        let samples = [
+            "rule", "r'[a-z]'", "'text'", "KEYWORD", "_", "_A", "label=", "^", "|", "&", ",", "+", "*", "?",
+            "{1,2}", "(", ")", "~", "$", ">", "!", ":", "@", "# comment", " \t", "\x0c", "\x0b", "\r\n", "\n",
+        ];
+        for sample in samples {
+            let first = sample.chars().next().unwrap();
+            let tried = TokenType::starting_with(first);
+            let matching: Vec<TokenType> =
+                TokenType::all().into_iter().filter(|token_type| token_type.get_regex().is_match(sample)).collect();
+            assert!(!matching.is_empty(), "{:?}", sample);
+            for token_type in matching {
+                assert!(tried.contains(&token_type), "{:?} is not tried for {:?}", token_type, sample);
+            }
+            // Ties are broken by the order of `TokenType::all`, so it is kept
+            let order: Vec<TokenType> = TokenType::all().into_iter().filter(|token_type| tried.contains(token_type)).collect();
+            assert_eq!(tried, order, "{:?}", sample);
+        }
+        assert!(TokenType::starting_with('é').is_empty());
+    }
+
+    #[test]
+    fn tokens_sharing_a_first_character_are_read_by_their_longest_match() {
+        let (tokens, diagnostics) = Tokenizer::new("r r'x' ra label= _ _A A".to_string()).tokenize();
+        assert!(diagnostics.is_empty());
+        let types: Vec<(&str, TokenType)> = tokens
+            .iter()
+            .filter(|token| token.token_type != TokenType::Newline)
+            .map(|token| (token.value.as_str(), token.token_type.clone()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("r", TokenType::RuleName),
+                ("x", TokenType::RegexLiteral),
+                ("ra", TokenType::RuleName),
+                ("label", TokenType::Label),
+                ("_", TokenType::RuleName),
+                ("_A", TokenType::Keyword),
+                ("A", TokenType::Keyword),
+            ]
+        );
+    }
+
+    /// Tokenizes the input and returns the values of its tokens, with `;` for each newline kept.
+    fn logical_lines(input: &str) -> String {
+        let (tokens, diagnostics) = Tokenizer::new(input.to_string()).tokenize();
+        assert!(diagnostics.is_empty());
+        let values: Vec<&str> = tokens
+            .iter()
+            .map(|token| if token.token_type == TokenType::Newline { ";" } else { token.value.as_str() })
+            .collect();
+        values.join(" ")
+    }
+
+    #[test]
+    fn lines_ending_with_an_operator_or_an_open_parenthesis_continue() {
+        assert_eq!(logical_lines("a: b |\nc\nd: 'd'\n"), "a : b | c ; d : d ;");
+        assert_eq!(logical_lines("a: b,\n'x'+\n"), "a : b , x + ;");
+        assert_eq!(logical_lines("a: (\nb\n| c\n)\nd: 'd'"), "a : ( b | c ) ; d : d ;");
+        assert_eq!(logical_lines("a:\n'a'\n"), "a : a ;");
+    }
+
+    #[test]
+    fn blank_and_comment_lines_do_not_end_a_continued_line() {
+        assert_eq!(logical_lines("a: b\n\n# note\n  c\nd: 'd'\n"), "a : b c ; d : d ;");
+        assert_eq!(logical_lines("a: b |\n\n  # note\nc\n"), "a : b | c ;");
+        assert_eq!(logical_lines("a: b\n# note\n\n| c\n"), "a : b | c ;");
+        assert_eq!(logical_lines("\n# header\n\na: b\n\n# note\nc: d\n"), "a : b ; c : d ;");
+    }
+
+    #[test]
+    fn positions_are_byte_offsets_and_columns_count_characters() {
+        let mut tokenizer = Tokenizer::new("a: 'é'\nb: '😀' c\n".to_string());
+        let (tokens, diagnostics) = tokenizer.tokenize();
+        assert!(diagnostics.is_empty());
+        let c = tokens.iter().find(|token| token.value == "c").unwrap();
+        assert_eq!((c.position, c.line, c.column), (18, 2, 8));
+        assert_eq!(tokenizer.get_column_utf16(c.position), 9);
+        let emoji = tokens.iter().find(|token| token.value == "😀").unwrap();
+        assert_eq!((emoji.position, emoji.length), (11, 6));
+    }
+
+    #[test]
+    fn unexpected_characters_span_all_their_bytes() {
+        let (_, diagnostics) = Tokenizer::new("a: é".to_string()).tokenize();
+        assert_eq!(diagnostics[0].message, "Unexpected character 'é'");
+        assert_eq!((diagnostics[0].start, diagnostics[0].end, diagnostics[0].column), (3, 5, 4));
+    }
+
+    #[test]
+    fn lines_end_with_every_kind_of_line_break() {
+        let mut tokenizer = Tokenizer::new("a: 'x'\rb: 'y'\r\nc: 'z'\nd: 'w'".to_string());
+        let (tokens, _) = tokenizer.tokenize();
+        let lines: Vec<(&str, usize, usize)> = tokens
+            .iter()
+            .filter(|token| token.token_type == TokenType::RuleName)
+            .map(|token| (token.value.as_str(), token.line, token.column))
+            .collect();
+        assert_eq!(lines, [("a", 1, 1), ("b", 2, 1), ("c", 3, 1), ("d", 4, 1)]);
+        assert_eq!(tokenizer.line_text(1), Some("a: 'x'"));
+        assert_eq!(tokenizer.line_text(2), Some("b: 'y'"));
+        assert_eq!(tokenizer.line_text(3), Some("c: 'z'"));
+        assert_eq!(tokenizer.line_text(4), Some("d: 'w'"));
+    }
+
+    #[test]
+    fn line_text_drops_the_line_ending() {
+        let tokenizer = Tokenizer::new("a: 'x'\r\nb: 'é'\n".to_string());
+        assert_eq!(tokenizer.line_text(1), Some("a: 'x'"));
+        assert_eq!(tokenizer.line_text(2), Some("b: 'é'"));
+        assert_eq!(tokenizer.line_text(3), Some(""));
+        assert_eq!(tokenizer.line_text(4), None);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod token_type;
 
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use self::token_type::TokenType;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -9,29 +12,43 @@ pub struct Token {
     pub token_type: TokenType,
     /// The value of the token.
     pub value: String,
-    /// The position of the token in the input string.
+    /// The byte offset of the token in the input string.
     pub position: usize,
     /// The line number of the token in the input string.
     pub line: usize,
     /// The column number of the token in the input string.
     pub column: usize,
+    /// The number of bytes the token spans in the input string.
+    pub length: usize,
+    /// The grammar file the token was read from, if the grammar was read from a file.
+    pub file: Option<Arc<PathBuf>>,
 }
 
 impl Token {
     /// Creates a new Token with the given type, value, position, line, and column.
     pub fn new(
         token_type: &TokenType,
-        value: &String,
+        value: &str,
         position: usize,
         line: usize,
         column: usize
     ) -> Token {
         Token {
             token_type: token_type.clone(),
-            value: value.clone(),
+            value: value.to_string(),
             position,
             line,
-            column
+            column,
+            length: value.len(),
+            file: None,
+        }
+    }
+
+    /// Describes where the token is, as `file:line:column` when the file is known.
+    pub fn location(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{}:{}:{}", file.display(), self.line, self.column),
+            None => format!("line {}, column {}", self.line, self.column),
         }
     }
 }
\ No newline at end of file
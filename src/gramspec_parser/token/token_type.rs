@@ -4,6 +4,7 @@
 
 lazy_static! {
     static ref RULE_NAME_REGEX: Regex = Regex::new(r"^[a-z_][a-z0-9_]*").unwrap();
+    static ref LABEL_REGEX: Regex = Regex::new(r"^[a-z_][a-z0-9_]*=").unwrap();
     static ref KEYWORD_REGEX: Regex = Regex::new(r"^[A-Z_][A-Z0-9_]*").unwrap();
     static ref REGEX_LITERAL_REGEX: Regex = Regex::new(r"^r'([^'\\]|\\.)*'").unwrap();
     static ref STRING_LITERAL_REGEX: Regex = Regex::new(r"^'([^'\\]|\\.)*'").unwrap();
@@ -13,15 +14,24 @@
     static ref REPEAT_ONE_REGEX: Regex = Regex::new(r"^\+").unwrap();
     static ref REPEAT_ZERO_REGEX: Regex = Regex::new(r"^\*").unwrap();
     static ref OPTIONAL_REGEX: Regex = Regex::new(r"^\?").unwrap();
+    static ref REPEAT_RANGE_REGEX: Regex = Regex::new(r"^\{\s*\d+\s*(,\s*\d*\s*)?\}").unwrap();
     static ref OPEN_PAREN_REGEX: Regex = Regex::new(r"^\(").unwrap();
     static ref CLOSE_PAREN_REGEX: Regex = Regex::new(r"^\)").unwrap();
     static ref DISCARD_REGEX: Regex = Regex::new(r"^~").unwrap();
     static ref META_REGEX: Regex = Regex::new(r"^\$").unwrap();
+    static ref LOOKAHEAD_REGEX: Regex = Regex::new(r"^>").unwrap();
+    static ref NOT_LOOKAHEAD_REGEX: Regex = Regex::new(r"^!").unwrap();
+    static ref CUT_REGEX: Regex = Regex::new(r"^\^").unwrap();
     static ref RULE_DEF_REGEX: Regex = Regex::new(r"^:").unwrap();
     static ref CONFIG_DIRECTIVE_REGEX: Regex = Regex::new(r"^@").unwrap();
     static ref COMMENT_REGEX: Regex = Regex::new(r"^#[^\r\n]*").unwrap();
     static ref WHITESPACE_REGEX: Regex = Regex::new(r"^[ \t\f\v]+").unwrap();
     static ref ENDLINE_REGEX: Regex = Regex::new(r"^(\r\n|\n|\r)+").unwrap();
+
+    /// The token types that can start with each ASCII character, in the order of `TokenType::all`.
+    static ref TOKEN_TYPES_BY_FIRST_CHAR: Vec<Vec<TokenType>> = (0..128u8)
+        .map(|c| TokenType::all().into_iter().filter(|token_type| token_type.can_start_with(c as char)).collect())
+        .collect();
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -31,6 +41,7 @@ pub enum TokenType {
     Keyword,
     RegexLiteral,
     StringLiteral,
+    Cut,
 
     // Operators
     Or,
@@ -39,10 +50,14 @@ pub enum TokenType {
     RepeatOne,
     RepeatZero,
     Optional,
+    RepeatRange,
     OpenParen,
     CloseParen,
     Discard,
     Meta,
+    Lookahead,
+    NotLookahead,
+    Label,
 
     // Special Characters
     RuleDefinition,
@@ -66,16 +81,50 @@ pub fn is_operator(&self) -> bool {
                 | TokenType::RepeatOne
                 | TokenType::RepeatZero
                 | TokenType::Optional
+                | TokenType::RepeatRange
                 | TokenType::Discard
                 | TokenType::Meta
+                | TokenType::Lookahead
+                | TokenType::NotLookahead
+                | TokenType::Label
         )
     }
 
+    /// Returns true if the token type is an operand of an expression, such as a rule name or a
+    /// literal.
+    pub fn is_operand(&self) -> bool {
+        matches!(
+            self,
+            TokenType::RuleName
+                | TokenType::Keyword
+                | TokenType::RegexLiteral
+                | TokenType::StringLiteral
+                | TokenType::Cut
+        )
+    }
+
+    /// Returns true if the token type is an opening or closing parenthesis.
+    pub fn is_paren(&self) -> bool {
+        matches!(self, TokenType::OpenParen | TokenType::CloseParen)
+    }
+
     /// Returns true if the token type is a unary operator.
     pub fn is_unary_operator(&self) -> bool {
         matches!(
             self,
-            TokenType::Optional | TokenType::RepeatOne | TokenType::RepeatZero | TokenType::Discard | TokenType::Meta
+            TokenType::Optional | TokenType::RepeatOne | TokenType::RepeatZero | TokenType::RepeatRange
+        ) || self.is_prefix_operator()
+    }
+
+    /// Returns true if the token type is a unary operator written before its operand.
+    pub fn is_prefix_operator(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Discard
+                | TokenType::Meta
+                | TokenType::Lookahead
+                | TokenType::NotLookahead
+                | TokenType::Label
         )
     }
 
@@ -87,14 +136,31 @@ pub fn is_binary_operator(&self) -> bool {
         )
     }
 
+    /// Returns true if an expression cannot end with this token, so a line ending with it
+    /// continues on the next line.
+    pub fn expects_operand(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Or
+                | TokenType::And
+                | TokenType::DelimitRepeat
+                | TokenType::OpenParen
+                | TokenType::RuleDefinition
+        ) || self.is_prefix_operator()
+    }
+
     /// Returns the precedence of the operator. Higher values indicate higher precedence.
     pub fn get_precedence(&self) -> u8 {
         match self {
             TokenType::Meta => 7,
             TokenType::Discard => 6,
+            TokenType::Lookahead => 6,
+            TokenType::NotLookahead => 6,
+            TokenType::Label => 6,
             TokenType::DelimitRepeat => 5,
             TokenType::RepeatOne => 4,
             TokenType::RepeatZero => 4,
+            TokenType::RepeatRange => 4,
             TokenType::Optional => 3,
             TokenType::And => 2,
             TokenType::Or => 1,
@@ -102,6 +168,44 @@ pub fn get_precedence(&self) -> u8 {
         }
     }
 
+    /// Returns true if a token of this type can start with the given character.
+    pub fn can_start_with(&self, c: char) -> bool {
+        match self {
+            TokenType::RuleName | TokenType::Label => c.is_ascii_lowercase() || c == '_',
+            TokenType::Keyword => c.is_ascii_uppercase() || c == '_',
+            TokenType::RegexLiteral => c == 'r',
+            TokenType::StringLiteral => c == '\'',
+            TokenType::Cut => c == '^',
+            TokenType::Or => c == '|',
+            TokenType::And => c == '&',
+            TokenType::DelimitRepeat => c == ',',
+            TokenType::RepeatOne => c == '+',
+            TokenType::RepeatZero => c == '*',
+            TokenType::Optional => c == '?',
+            TokenType::RepeatRange => c == '{',
+            TokenType::OpenParen => c == '(',
+            TokenType::CloseParen => c == ')',
+            TokenType::Discard => c == '~',
+            TokenType::Meta => c == '$',
+            TokenType::Lookahead => c == '>',
+            TokenType::NotLookahead => c == '!',
+            TokenType::RuleDefinition => c == ':',
+            TokenType::ConfigDirective => c == '@',
+            TokenType::Comment => c == '#',
+            TokenType::Whitespace => matches!(c, ' ' | '\t' | '\x0c' | '\x0b'),
+            TokenType::Newline => matches!(c, '\n' | '\r'),
+        }
+    }
+
+    /// Returns the token types that can start with the given character, so that only their
+    /// regexes have to be tried.
+    pub fn starting_with(c: char) -> &'static [TokenType] {
+        match TOKEN_TYPES_BY_FIRST_CHAR.get(c as usize) {
+            Some(token_types) => token_types,
+            None => &[],
+        }
+    }
+
     /// Returns the compiled regex pattern for the token type.
     pub fn get_regex(&self) -> &'static Regex {
         match self {
@@ -109,16 +213,21 @@ pub fn get_regex(&self) -> &'static Regex {
             TokenType::Keyword => &KEYWORD_REGEX,
             TokenType::RegexLiteral => &REGEX_LITERAL_REGEX,
             TokenType::StringLiteral => &STRING_LITERAL_REGEX,
+            TokenType::Cut => &CUT_REGEX,
             TokenType::Or => &OR_REGEX,
             TokenType::And => &AND_REGEX,
             TokenType::DelimitRepeat => &DELIMIT_REPEAT_REGEX,
             TokenType::RepeatOne => &REPEAT_ONE_REGEX,
             TokenType::RepeatZero => &REPEAT_ZERO_REGEX,
             TokenType::Optional => &OPTIONAL_REGEX,
+            TokenType::RepeatRange => &REPEAT_RANGE_REGEX,
             TokenType::OpenParen => &OPEN_PAREN_REGEX,
             TokenType::CloseParen => &CLOSE_PAREN_REGEX,
             TokenType::Discard => &DISCARD_REGEX,
             TokenType::Meta => &META_REGEX,
+            TokenType::Lookahead => &LOOKAHEAD_REGEX,
+            TokenType::NotLookahead => &NOT_LOOKAHEAD_REGEX,
+            TokenType::Label => &LABEL_REGEX,
             TokenType::RuleDefinition => &RULE_DEF_REGEX,
             TokenType::ConfigDirective => &CONFIG_DIRECTIVE_REGEX,
             TokenType::Comment => &COMMENT_REGEX,
@@ -128,15 +237,17 @@ pub fn get_regex(&self) -> &'static Regex {
     }
 
     /// Transforms the value of the token based on its type. For example, it removes surrounding quotes from string literals.
-    pub fn transform(&self, value: &String) -> String {
+    /// Returns None if a string literal contains an invalid escape sequence.
+    pub fn transform(&self, value: &str) -> Option<String> {
         match self {
             TokenType::RegexLiteral => {
-                let string = value.strip_prefix('r').unwrap();
-                string[1..string.len() - 1].to_string()
+                let string = value.strip_prefix('r')?;
+                Some(string[1..string.len() - 1].to_string())
             }
-            TokenType::StringLiteral => unescape(&value[1..value.len() - 1]).unwrap(),
+            TokenType::StringLiteral => unescape(&value[1..value.len() - 1]),
+            TokenType::Label => Some(value.strip_suffix('=')?.to_string()),
             // No transformation needed for other token types
-            _ => value.clone(),
+            _ => Some(value.to_string()),
         }
     }
 
@@ -146,6 +257,7 @@ pub fn all() -> Vec<TokenType> {
             TokenType::StringLiteral,
             TokenType::RuleName,
             TokenType::Keyword,
+            TokenType::Cut,
             // Operators
             TokenType::Or,
             TokenType::And,
@@ -153,10 +265,14 @@ pub fn all() -> Vec<TokenType> {
             TokenType::RepeatOne,
             TokenType::RepeatZero,
             TokenType::Optional,
+            TokenType::RepeatRange,
             TokenType::OpenParen,
             TokenType::CloseParen,
             TokenType::Discard,
             TokenType::Meta,
+            TokenType::Lookahead,
+            TokenType::NotLookahead,
+            TokenType::Label,
             // Special Characters
             TokenType::RuleDefinition,
             TokenType::ConfigDirective,
@@ -0,0 +1,1061 @@
+pub mod diagnostic;
+pub mod node;
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use regex::Regex;
+
+use crate::generator::Generator;
+use crate::gramspec_parser::gramspec::{GramSpec, anchored_regex};
+use crate::gramspec_parser::gramspec::expression::Expression;
+use crate::gramspec_parser::gramspec::precedence::{Associativity, PrecedenceLevel};
+use diagnostic::Diagnostic;
+use node::{DISCARD_NODE_TYPE, ERROR_NODE_TYPE, MISSING_NODE_TYPE, Node, STRING_NODE_TYPE};
+
+/// The nodes matched by an alternative, and whether a cut committed to it.
+type Alternative = (Option<Vec<Node>>, bool);
+
+/// An operator of a precedence rule: its level, associativity and the expression matching it.
+type Operator<'a> = (usize, Associativity, &'a Expression);
+
+/// An operator matched in the input, with the nodes of its match.
+type MatchedOperator<'a> = (Operator<'a>, Vec<Node>);
+
+/// The nodes of a precedence rule match, and whether an operator was applied.
+type Climbed = (Vec<Node>, bool);
+
+/// Parses input directly from a grammar specification, without generating code.
+///
+/// The interpreter follows the same rules as the parsers produced by the generator: the
+/// longest matching alternative wins, left-recursive rules are grown from a seed, discarded
+/// expressions are dropped from the tree and meta rules are inlined into their parent.
+pub struct Interpreter<'a> {
+    gramspec: &'a GramSpec,
+    position: usize,
+    content: String,
+    memos: HashMap<usize, HashMap<String, Option<Vec<Node>>>>,
+    circular_rules: HashMap<String, bool>,
+    regexes: HashMap<String, Regex>,
+    ignore_between: Vec<Regex>,
+
+    /// Descriptions of what was expected at the furthest position any expression failed at.
+    expected: Vec<String>,
+    last_position: usize,
+    /// Whether a rule that fails where it starts is reported as expected, instead of its contents.
+    expect_rule_names: bool,
+    cut: bool,
+    /// Whether a lookahead or an optional part is being matched, which a failure after a cut
+    /// inside it only fails.
+    cut_scoped: bool,
+
+    /// Whether rules declared with `@recover` recover from failures.
+    recovering: bool,
+    /// Where a recovering rule failed while it is matched again with a missing literal inserted.
+    missing_at: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(gramspec: &'a GramSpec) -> Self {
+        Interpreter {
+            gramspec,
+            position: 0,
+            content: String::new(),
+            memos: HashMap::new(),
+            circular_rules: HashMap::new(),
+            regexes: HashMap::new(),
+            ignore_between: Vec::new(),
+            expected: Vec::new(),
+            last_position: 0,
+            expect_rule_names: false,
+            cut: false,
+            cut_scoped: false,
+            recovering: false,
+            missing_at: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Reports rules that fail where they start by their name in error messages, such as
+    /// "expected statement", instead of by the literals they start with.
+    pub fn expect_rule_names(mut self) -> Self {
+        self.expect_rule_names = true;
+        self
+    }
+
+    /// Parses the input, starting at the entry rule of the grammar.
+    pub fn parse(&mut self, input: &str) -> Result<Node, Box<dyn Error>> {
+        self.reset(input)?;
+
+        let entry_rule = self.gramspec.config.entry_rule.clone();
+        if let Some(nodes) = self.call_rule(&entry_rule, false)? {
+            // Trailing trivia, such as a final newline, does not have to be matched by the grammar
+            self.skip_trivia();
+            if self.position == self.content.len() {
+                let nodes = Self::clear_discard_nodes(nodes);
+                return Ok(nodes[0].clone());
+            }
+            self.record_failure(|| String::from("end of input"));
+        }
+        Err(self.parse_error())
+    }
+
+    /// Parses the start of the input with the entry rule, without requiring the whole input to
+    /// match. Returns the tree and the number of bytes it consumed.
+    pub fn parse_prefix(&mut self, input: &str) -> Result<(Node, usize), Box<dyn Error>> {
+        self.reset(input)?;
+
+        let entry_rule = self.gramspec.config.entry_rule.clone();
+        if let Some(nodes) = self.call_rule(&entry_rule, false)? {
+            let nodes = Self::clear_discard_nodes(nodes);
+            return Ok((nodes[0].clone(), self.position));
+        }
+        Err(self.parse_error())
+    }
+
+    /// Parses the input like [`Interpreter::parse`], but recovers from errors in the rules declared
+    /// with `@recover`. Returns the tree, with error and missing nodes where the input could not be
+    /// parsed, and the diagnostics describing them.
+    pub fn parse_with_recovery(&mut self, input: &str) -> Result<(Node, Vec<Diagnostic>), Box<dyn Error>> {
+        // Recovery can change which alternatives match, so it is only used when the input does not parse
+        if let Ok(node) = self.parse(input) {
+            return Ok((node, Vec::new()));
+        }
+
+        self.reset(input)?;
+        self.recovering = true;
+        let result = self.parse_recovering();
+        self.recovering = false;
+        result
+    }
+
+    fn parse_recovering(&mut self) -> Result<(Node, Vec<Diagnostic>), Box<dyn Error>> {
+        let entry_rule = self.gramspec.config.entry_rule.clone();
+        let Some(nodes) = self.call_rule(&entry_rule, false)? else {
+            return Err(self.parse_error());
+        };
+        let mut root = Self::clear_discard_nodes(nodes)[0].clone();
+
+        // Input after the end of the tree is kept in a final error node
+        self.skip_trivia();
+        if self.position < self.content.len() {
+            let start_pos = self.position;
+            self.record_failure(|| String::from("end of input"));
+            let (failure_pos, expected) = (self.last_position, self.expected.clone());
+            self.position = self.content.len();
+            root.children.push(self.error_node(start_pos, failure_pos, &expected));
+        }
+        Ok((root, std::mem::take(&mut self.diagnostics)))
+    }
+
+    fn reset(&mut self, input: &str) -> Result<(), Box<dyn Error>> {
+        self.position = 0;
+        self.content = input.to_string();
+        self.memos.clear();
+        self.expected.clear();
+        self.last_position = 0;
+        self.cut = false;
+        self.cut_scoped = false;
+        self.missing_at = None;
+        self.diagnostics.clear();
+        self.ignore_between = self
+            .gramspec
+            .config
+            .ignore_between
+            .iter()
+            .map(|regex| Regex::new(&anchored_regex(regex)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn parse_error(&self) -> Box<dyn Error> {
+        format!(
+            "{}\n{}",
+            self.describe_failure(self.last_position, &self.expected),
+            self.snippet(self.last_position)
+        )
+        .into()
+    }
+
+    /// Describes a failure as what was expected and what was found instead, such as
+    /// "expected ')' or ',' but found 'x' at line 3, column 14".
+    fn describe_failure(&self, position: usize, expected: &[String]) -> String {
+        let found = match self.content[position..].chars().next() {
+            Some(c) => format!("'{}'", c.escape_debug()),
+            None => String::from("end of input"),
+        };
+        let (line, col, _) = self.get_line_col(position);
+        let expected = match expected {
+            [] => return format!("unexpected {} at line {}, column {}", found, line, col),
+            [single] => single.clone(),
+            [first, second] => format!("{} or {}", first, second),
+            [rest @ .., last] => format!("one of {} or {}", rest.join(", "), last),
+        };
+        format!("expected {} but found {} at line {}, column {}", expected, found, line, col)
+    }
+
+    /// Returns the line of the input containing `position`, with a caret under its column.
+    fn snippet(&self, position: usize) -> String {
+        let (line, col, _) = self.get_line_col(position);
+        let text = self.content.lines().nth(line - 1).unwrap_or("");
+        let number = line.to_string();
+        format!("{} | {}\n{} | {}^", number, text, " ".repeat(number.len()), " ".repeat(col - 1))
+    }
+
+    /// Reads and parses the given file.
+    pub fn parse_file(&mut self, file_path: &str) -> Result<Node, Box<dyn Error>> {
+        let content = std::fs::read_to_string(file_path)?;
+        self.parse(&content)
+    }
+
+    /// Returns the line of a byte offset in the input, and its column both in characters and in
+    /// UTF-16 code units, all counting from 1.
+    fn get_line_col(&self, position: usize) -> (usize, usize, usize) {
+        let before = &self.content[..position];
+        let line = before.matches('\n').count() + 1;
+        let line_text = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        (line, line_text.chars().count() + 1, line_text.encode_utf16().count() + 1)
+    }
+
+    fn clear_discard_nodes(nodes: Vec<Node>) -> Vec<Node> {
+        nodes
+            .into_iter()
+            .filter(|node| node.node_type != DISCARD_NODE_TYPE)
+            .map(|mut node| {
+                node.children = Self::clear_discard_nodes(node.children);
+                node
+            })
+            .collect()
+    }
+
+    fn is_left_circular(&mut self, rule_name: &str) -> bool {
+        if let Some(circular) = self.circular_rules.get(rule_name) {
+            return *circular;
+        }
+        let circular = self.gramspec.is_left_circular(rule_name);
+        self.circular_rules.insert(rule_name.to_string(), circular);
+        circular
+    }
+
+    /// Records that `expected` failed to match at the current position, if no expression failed
+    /// further into the input.
+    fn record_failure(&mut self, expected: impl FnOnce() -> String) {
+        if self.position > self.last_position {
+            self.last_position = self.position;
+            self.expected.clear();
+        }
+        if self.position == self.last_position {
+            let expected = expected();
+            if !self.expected.contains(&expected) {
+                self.expected.push(expected);
+            }
+        }
+    }
+
+    /// Restores the furthest failure saved before a nested attempt, unless the attempt failed
+    /// further into the input. Failures at the same position are combined.
+    fn restore_failure(&mut self, position: usize, expected: Vec<String>) {
+        if position > self.last_position || (position == self.last_position && self.expected.is_empty()) {
+            self.last_position = position;
+            self.expected = expected;
+        } else if position == self.last_position {
+            let attempted = std::mem::replace(&mut self.expected, expected);
+            for item in attempted {
+                if !self.expected.contains(&item) {
+                    self.expected.push(item);
+                }
+            }
+        }
+    }
+
+    fn circular_wrapper(&mut self, rule_name: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let pos = self.position;
+
+        if let Some(cached_result) = self.memos.get(&pos).and_then(|memo| memo.get(rule_name)) {
+            let cached_result = cached_result.clone();
+
+            let end_pos = cached_result
+                .as_ref()
+                .and_then(|nodes| nodes.iter().map(|n| n.get_end_pos()).max())
+                .unwrap_or(pos);
+            self.position = end_pos;
+
+            return Ok(cached_result);
+        }
+
+        self.memos.entry(pos).or_default().insert(rule_name.to_string(), None);
+
+        let mut last_result = None;
+        let mut last_pos = pos;
+
+        loop {
+            self.position = pos;
+
+            let result = self.call_rule(rule_name, false)?;
+            let end_pos = self.position;
+
+            if end_pos <= last_pos {
+                break;
+            }
+
+            last_result = result;
+            last_pos = end_pos;
+
+            if let Some(memo) = self.memos.get_mut(&pos) {
+                memo.insert(rule_name.to_string(), last_result.clone());
+            }
+        }
+
+        // If the result was a failure, remove it from the cache to prevent poisoning
+        if last_result.is_none()
+            && let Some(memo) = self.memos.get_mut(&pos)
+        {
+            memo.remove(rule_name);
+        }
+
+        self.position = last_pos;
+        Ok(last_result)
+    }
+
+    fn expect_string(&mut self, string: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let initial_pos = self.position;
+        let mut start_pos = self.position;
+        loop {
+            if self.content[self.position..].starts_with(string) {
+                self.position += string.len();
+                return Ok(Some(vec![Node::new_with_position(
+                    STRING_NODE_TYPE,
+                    vec![],
+                    Some(string.to_string()),
+                    start_pos,
+                    self.position,
+                )]));
+            }
+            self.ignore_between();
+            if start_pos == self.position {
+                break;
+            }
+            start_pos = self.position;
+        }
+        self.record_failure(|| format!("'{}'", string.escape_debug()));
+        self.position = initial_pos;
+        Ok(None)
+    }
+
+    fn expect_regex(&mut self, regex: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        if !self.regexes.contains_key(regex) {
+            let compiled = Regex::new(&anchored_regex(regex))
+                .map_err(|err| format!("Invalid regex '{}': {}", regex, err))?;
+            self.regexes.insert(regex.to_string(), compiled);
+        }
+
+        let initial_pos = self.position;
+        let mut start_pos = self.position;
+        loop {
+            let found = self.regexes[regex]
+                .find(&self.content[self.position..])
+                .map(|found| found.as_str().to_string());
+            if let Some(found) = found {
+                self.position += found.len();
+                return Ok(Some(vec![Node::new_with_position(
+                    STRING_NODE_TYPE,
+                    vec![],
+                    Some(found),
+                    start_pos,
+                    self.position,
+                )]));
+            }
+            self.ignore_between();
+            if start_pos == self.position {
+                break;
+            }
+            start_pos = self.position;
+        }
+        self.record_failure(|| format!("r'{}'", regex));
+        self.position = initial_pos;
+        Ok(None)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let keyword_value = self
+            .gramspec
+            .keyword_value(keyword)
+            .ok_or_else(|| format!("Unknown keyword: {}", keyword))?;
+        // Keywords match like strings, but their nodes hold the keyword name
+        let nodes = self.expect_string(keyword_value)?;
+        Ok(nodes.map(|nodes| {
+            nodes
+                .into_iter()
+                .map(|mut node| {
+                    node.value = Some(keyword.to_string());
+                    node
+                })
+                .collect()
+        }))
+    }
+
+    /// Evaluates an expression. A cut inside a lookahead, an optional part or a repetition only
+    /// commits to the choices inside it, not to the alternative around it. A failure after a cut
+    /// inside a lookahead or an optional part only fails that part, while inside a repetition it
+    /// stops parsing, as it does outside of it.
+    fn eval(&mut self, expression: &Expression) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let scopes_cut = matches!(
+            expression,
+            Expression::Lookahead(_)
+                | Expression::NotLookahead(_)
+                | Expression::Optional(_)
+                | Expression::RepeatZero(_)
+                | Expression::RepeatOne(_)
+                | Expression::RepeatRange(..)
+                | Expression::DelimitRepeatZero(..)
+                | Expression::DelimitRepeatOne(..)
+                | Expression::DelimitRepeatRange(..)
+        );
+        if !scopes_cut {
+            return self.eval_expression(expression);
+        }
+        let outer_cut = std::mem::replace(&mut self.cut, false);
+        let outer_cut_scoped = self.cut_scoped;
+        self.cut_scoped |= matches!(expression, Expression::Lookahead(_) | Expression::NotLookahead(_) | Expression::Optional(_));
+        let result = self.eval_expression(expression);
+        self.cut = outer_cut;
+        self.cut_scoped = outer_cut_scoped;
+        result
+    }
+
+    fn eval_expression(&mut self, expression: &Expression) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        match expression {
+            Expression::RuleName(rule) => {
+                if self.recovering && self.gramspec.recovery.contains_key(&rule.value) {
+                    return self.call_rule_recovering(&rule.value);
+                }
+                if self.expect_rule_names {
+                    return self.call_rule_expecting_name(&rule.value);
+                }
+                self.call_rule(&rule.value, true)
+            }
+            Expression::RegexLiteral(regex) => self.expect_regex(&regex.value),
+            Expression::StringLiteral(string) => self.expect_string(&string.value),
+            Expression::Keyword(keyword) => self.expect_keyword(&keyword.value),
+            Expression::Cut(_) => {
+                // Commit to the current alternative
+                self.cut = true;
+                Ok(Some(vec![]))
+            }
+            Expression::EndOfInput(_) => {
+                let start_pos = self.position;
+                self.skip_trivia();
+                if self.position == self.content.len() {
+                    return Ok(Some(vec![]));
+                }
+                self.record_failure(|| String::from("end of input"));
+                self.position = start_pos;
+                Ok(None)
+            }
+            Expression::Or(left, right) => {
+                let start_pos = self.position;
+                let (left_nodes, left_committed) = self.eval_alternative(left)?;
+                if left_committed {
+                    return Ok(left_nodes);
+                }
+                let left_end = self.position;
+                self.position = start_pos;
+                let (right_nodes, right_committed) = self.eval_alternative(right)?;
+                let right_end = self.position;
+
+                if left_nodes.is_some() && !right_committed && (right_nodes.is_none() || left_end >= right_end) {
+                    self.position = left_end;
+                    Ok(left_nodes)
+                } else if right_nodes.is_some() {
+                    self.position = right_end;
+                    Ok(right_nodes)
+                } else {
+                    self.position = start_pos;
+                    Ok(None)
+                }
+            }
+            Expression::And(left, right) => {
+                let start_pos = self.position;
+                let Some(mut nodes) = self.eval(left)? else {
+                    return Ok(None);
+                };
+                let right_nodes = match self.eval(right)? {
+                    Some(right_nodes) => right_nodes,
+                    None => match self.missing_node(right) {
+                        Some(missing) => vec![missing],
+                        None => {
+                            self.position = start_pos;
+                            return Ok(None);
+                        }
+                    },
+                };
+                nodes.extend(right_nodes);
+                Ok(Some(nodes))
+            }
+            Expression::DelimitRepeatOne(expression, delimiter) => {
+                // Attempt to parse the first expression
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(None);
+                };
+                Ok(Some(self.eval_delimited_tail(expression, delimiter, nodes)?))
+            }
+            Expression::DelimitRepeatZero(expression, delimiter) => {
+                // Attempt to parse the first expression
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(Some(vec![]));
+                };
+                Ok(Some(self.eval_delimited_tail(expression, delimiter, nodes)?))
+            }
+            Expression::RepeatOne(expression) => {
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(None);
+                };
+                Ok(Some(self.eval_repeat_tail(expression, nodes)?))
+            }
+            Expression::RepeatZero(expression) => {
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(Some(vec![]));
+                };
+                Ok(Some(self.eval_repeat_tail(expression, nodes)?))
+            }
+            Expression::Optional(expression) => Ok(Some(self.eval(expression)?.unwrap_or_default())),
+            Expression::RepeatRange(expression, min, max) => self.eval_repeat_range(expression, None, *min, *max),
+            Expression::DelimitRepeatRange(expression, delimiter, min, max) => {
+                self.eval_repeat_range(expression, Some(delimiter), *min, *max)
+            }
+            Expression::Discard(expression) => {
+                let start_pos = self.position;
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(None);
+                };
+                let Some(last_node) = nodes.last() else {
+                    return Ok(Some(vec![]));
+                };
+                let node = Node::new_with_position(DISCARD_NODE_TYPE, vec![], None, start_pos, last_node.get_end_pos());
+                Ok(Some(vec![node]))
+            }
+            Expression::Meta(expression) => {
+                let Some(nodes) = self.eval(expression)? else {
+                    return Ok(None);
+                };
+                // Inline the children of the matched rule nodes into the parent
+                Ok(Some(nodes.into_iter().flat_map(|node| node.children).collect()))
+            }
+            Expression::Lookahead(expression) => {
+                // Match without consuming any input
+                let start_pos = self.position;
+                let nodes = self.eval(expression)?;
+                self.position = start_pos;
+                Ok(nodes.map(|_| vec![]))
+            }
+            Expression::Label(label, expression) => {
+                let Some(mut nodes) = self.eval(expression)? else {
+                    return Ok(None);
+                };
+                // Labels given inside the expression, such as those of an inlined meta rule, are kept
+                for node in &mut nodes {
+                    node.label.get_or_insert_with(|| label.clone());
+                }
+                Ok(Some(nodes))
+            }
+            Expression::NotLookahead(expression) => {
+                // Succeed without consuming any input only if the expression does not match. What the
+                // expression fails to match was not expected, so its failures are not recorded
+                let start_pos = self.position;
+                let failure = (self.last_position, self.expected.clone());
+                let nodes = self.eval(expression)?;
+                self.position = start_pos;
+                (self.last_position, self.expected) = failure;
+                Ok(if nodes.is_some() { None } else { Some(vec![]) })
+            }
+        }
+    }
+
+    /// Matches `(delimiter expression)*` after the first repetition of a delimited repeat.
+    fn eval_delimited_tail(
+        &mut self,
+        expression: &Expression,
+        delimiter: &Expression,
+        mut nodes: Vec<Node>,
+    ) -> Result<Vec<Node>, Box<dyn Error>> {
+        loop {
+            let start = self.position;
+            let Some(delimiter_nodes) = self.eval(delimiter)? else {
+                break;
+            };
+            let Some(expression_nodes) = self.eval(expression)? else {
+                self.position = start;
+                break;
+            };
+
+            // Only if both delimiter and expression are successful, append them to the nodes
+            nodes.extend(delimiter_nodes);
+            nodes.extend(expression_nodes);
+
+            // Prevent infinite loops by checking if position has advanced
+            if self.position <= start {
+                break;
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Matches further repetitions of an expression after its first match.
+    fn eval_repeat_tail(&mut self, expression: &Expression, mut nodes: Vec<Node>) -> Result<Vec<Node>, Box<dyn Error>> {
+        let mut last_pos = self.position;
+        while let Some(new_nodes) = self.eval(expression)? {
+            nodes.extend(new_nodes);
+            if self.position == last_pos {
+                break;
+            }
+            last_pos = self.position;
+        }
+        Ok(nodes)
+    }
+
+    /// Matches an expression between `min` and `max` times, optionally separated by a delimiter.
+    /// Matching stops after `max` repetitions and leaves any further input to the rest of the rule.
+    fn eval_repeat_range(
+        &mut self,
+        expression: &Expression,
+        delimiter: Option<&Expression>,
+        min: usize,
+        max: Option<usize>,
+    ) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let start_pos = self.position;
+        let mut nodes = Vec::new();
+        let mut count = 0;
+        while max.is_none_or(|max| count < max) {
+            let start = self.position;
+            let mut new_nodes = Vec::new();
+            if count > 0
+                && let Some(delimiter) = delimiter
+            {
+                let Some(delimiter_nodes) = self.eval(delimiter)? else {
+                    break;
+                };
+                new_nodes = delimiter_nodes;
+            }
+            let Some(expression_nodes) = self.eval(expression)? else {
+                self.position = start;
+                break;
+            };
+
+            new_nodes.extend(expression_nodes);
+            nodes.extend(new_nodes);
+            count += 1;
+
+            // An expression that matches without consuming input would match every remaining time
+            if self.position == start {
+                count = count.max(min);
+                break;
+            }
+        }
+
+        if count < min {
+            self.position = start_pos;
+            return Ok(None);
+        }
+        Ok(Some(nodes))
+    }
+
+    /// Evaluates one alternative of a choice, returning whether a cut committed to it. If the
+    /// alternative fails after a cut, parsing stops with an error instead of backtracking, unless
+    /// the choice is inside a lookahead or an optional part, which fails instead.
+    fn eval_alternative(&mut self, expression: &Expression) -> Result<Alternative, Box<dyn Error>> {
+        let outer_cut = self.cut;
+        self.cut = false;
+        let result = self.eval(expression);
+        let committed = self.cut;
+        self.cut = outer_cut;
+
+        let nodes = result?;
+        // While recovering, the failure is left to the enclosing recovering rule
+        if committed && nodes.is_none() && !self.recovering && !self.cut_scoped {
+            return Err(self.parse_error());
+        }
+        Ok((nodes, committed))
+    }
+
+    fn get_longest_expression_match(&mut self, expressions: &[Expression]) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let start_pos = self.position;
+        let mut longest_end = start_pos;
+        let mut longest_nodes = None;
+
+        for expr in expressions {
+            let (result, committed) = self.eval_alternative(expr)?;
+            let new_end_pos = self.position;
+            self.position = start_pos; // Reset position to start for each expression evaluation
+            if committed {
+                // A committed alternative wins without trying the remaining ones
+                longest_end = new_end_pos;
+                longest_nodes = result;
+                break;
+            }
+            if result.is_some() && (new_end_pos > longest_end || longest_nodes.is_none()) {
+                longest_end = new_end_pos;
+                longest_nodes = result;
+            }
+        }
+        self.position = longest_end;
+        Ok(longest_nodes)
+    }
+
+    /// Parses an operand followed by the operators binding at least as tightly as `min_level`, by
+    /// precedence climbing. Returns the nodes and whether an operator was applied, in which case
+    /// they are a single node of `node_type` holding the operands and the operator.
+    fn eval_precedence(
+        &mut self,
+        operand: &[Expression],
+        operators: &[Operator],
+        min_level: usize,
+        node_type: &str,
+    ) -> Result<Option<Climbed>, Box<dyn Error>> {
+        let start_pos = self.position;
+        let (mut nodes, mut applied) = match self.match_operator(operators, true)? {
+            Some(((level, _, _), operator_nodes)) => {
+                let Some((operand_nodes, _)) = self.eval_precedence(operand, operators, level, node_type)? else {
+                    self.position = start_pos;
+                    return Ok(None);
+                };
+                let parts = vec![("operator", operator_nodes), ("operand", operand_nodes)];
+                (vec![self.operator_node(node_type, parts, start_pos)], true)
+            }
+            None => match self.get_longest_expression_match(operand)? {
+                Some(nodes) => (nodes, false),
+                None => return Ok(None),
+            },
+        };
+
+        let mut nonassoc_level = None;
+        loop {
+            let operator_pos = self.position;
+            let Some(((level, associativity, _), operator_nodes)) = self.match_operator(operators, false)? else {
+                break;
+            };
+            // Looser operators are left to the caller, and non-associative ones cannot be chained
+            if level < min_level || nonassoc_level == Some(level) {
+                self.position = operator_pos;
+                break;
+            }
+
+            let parts = if associativity == Associativity::Postfix {
+                vec![("operand", nodes), ("operator", operator_nodes)]
+            } else {
+                let right_level = if associativity == Associativity::Right { level } else { level + 1 };
+                let Some((right_nodes, _)) = self.eval_precedence(operand, operators, right_level, node_type)? else {
+                    self.position = operator_pos;
+                    break;
+                };
+                if associativity == Associativity::NonAssoc {
+                    nonassoc_level = Some(level);
+                }
+                vec![("left", nodes), ("operator", operator_nodes), ("right", right_nodes)]
+            };
+            nodes = vec![self.operator_node(node_type, parts, start_pos)];
+            applied = true;
+        }
+
+        Ok(Some((nodes, applied)))
+    }
+
+    /// Matches the longest of the prefix operators, or of the binary and postfix operators.
+    fn match_operator<'o>(
+        &mut self,
+        operators: &[Operator<'o>],
+        prefix: bool,
+    ) -> Result<Option<MatchedOperator<'o>>, Box<dyn Error>> {
+        let start_pos = self.position;
+        let mut longest: Option<(Operator, Vec<Node>, usize)> = None;
+        for &operator in operators {
+            if (operator.1 == Associativity::Prefix) != prefix {
+                continue;
+            }
+            self.position = start_pos;
+            if let Some(nodes) = self.eval(operator.2)?
+                && longest.as_ref().is_none_or(|(_, _, end)| self.position > *end)
+            {
+                longest = Some((operator, nodes, self.position));
+            }
+        }
+        match longest {
+            Some((operator, nodes, end)) => {
+                self.position = end;
+                Ok(Some((operator, nodes)))
+            }
+            None => {
+                self.position = start_pos;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Builds the node of an operator application, labelling the nodes of each part.
+    fn operator_node(&self, node_type: &str, parts: Vec<(&str, Vec<Node>)>, start_pos: usize) -> Node {
+        let mut children = Vec::new();
+        for (label, nodes) in parts {
+            for mut node in nodes {
+                node.label.get_or_insert_with(|| label.to_string());
+                children.push(node);
+            }
+        }
+        Node::new_with_position(node_type, children, None, start_pos, self.position)
+    }
+
+    fn call_rule(&mut self, rule_name: &str, protected: bool) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        if protected && self.is_left_circular(rule_name) {
+            return self.circular_wrapper(rule_name);
+        }
+
+        let gramspec = self.gramspec;
+        let start_pos = self.position;
+        if let (Some(expressions), Some(levels)) = (gramspec.rules.get(rule_name), gramspec.precedence.get(rule_name)) {
+            let node_type = Generator::to_pascal_case(rule_name);
+            let operators: Vec<Operator> = levels
+                .iter()
+                .enumerate()
+                .flat_map(|(level_index, level): (usize, &PrecedenceLevel)| {
+                    level.operators.iter().map(move |operator| (level_index, level.associativity, operator))
+                })
+                .collect();
+            match self.eval_precedence(expressions, &operators, 0, &node_type)? {
+                Some((nodes, true)) => Ok(Some(nodes)),
+                // A lone operand is wrapped in a node of the rule, like the match of any other rule
+                Some((nodes, false)) => Ok(Some(vec![Node::new_with_position(&node_type, nodes, None, start_pos, self.position)])),
+                None => Ok(None),
+            }
+        } else if let Some(expressions) = gramspec.rules.get(rule_name) {
+            let Some(matches) = self.get_longest_expression_match(expressions)? else {
+                return Ok(None);
+            };
+            let node_type = Generator::to_pascal_case(rule_name);
+            Ok(Some(vec![Node::new_with_position(&node_type, matches, None, start_pos, self.position)]))
+        } else if let Some(expressions) = gramspec.meta_rules.get(rule_name) {
+            self.get_longest_expression_match(expressions)
+        } else if let Some(expressions) = gramspec.discard_rules.get(rule_name) {
+            if self.get_longest_expression_match(expressions)?.is_none() {
+                return Ok(None);
+            }
+            Ok(Some(vec![Node::new_with_position(DISCARD_NODE_TYPE, vec![], None, start_pos, self.position)]))
+        } else {
+            Err(format!("Unknown rule: {}", rule_name).into())
+        }
+    }
+
+    /// Calls a rule, reporting the rule by its name instead of by what it expects if it fails where
+    /// it starts.
+    fn call_rule_expecting_name(&mut self, rule_name: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let start_pos = self.position;
+        self.skip_trivia();
+        let content_start = self.position;
+        self.position = start_pos;
+        let expected_len = if self.last_position == content_start { self.expected.len() } else { 0 };
+
+        let nodes = self.call_rule(rule_name, true)?;
+        if nodes.is_none() && self.last_position == content_start {
+            self.expected.truncate(expected_len);
+            self.position = content_start;
+            self.record_failure(|| rule_name.to_string());
+            self.position = start_pos;
+        }
+        Ok(nodes)
+    }
+
+    /// Calls a rule declared with `@recover`. If the rule fails after matching part of the input,
+    /// it is matched again with one missing literal inserted where it failed. If that fails too,
+    /// the input up to the end of the next match of a synchronisation expression is replaced by an
+    /// error node.
+    fn call_rule_recovering(&mut self, rule_name: &str) -> Result<Option<Vec<Node>>, Box<dyn Error>> {
+        let start_pos = self.position;
+        self.skip_trivia();
+        let content_start = self.position;
+        self.position = start_pos;
+        let diagnostics_len = self.diagnostics.len();
+
+        // Track how far this call gets on its own, keeping the furthest failure of the whole parse
+        let outer_position = self.last_position;
+        let outer_expected = std::mem::take(&mut self.expected);
+        self.last_position = content_start;
+        let nodes = self.call_rule(rule_name, true)?;
+        let (failure_pos, failure_expected) = (self.last_position, self.expected.clone());
+        self.restore_failure(outer_position, outer_expected);
+        if nodes.is_some() || failure_pos <= content_start {
+            return Ok(nodes);
+        }
+
+        self.diagnostics.truncate(diagnostics_len);
+        self.position = start_pos;
+        self.missing_at = Some(failure_pos);
+        let nodes = self.call_rule(rule_name, true)?;
+        self.missing_at = None;
+        if nodes.is_some() {
+            return Ok(nodes);
+        }
+
+        // Skip to the end of the next synchronisation expression, or to the end of the input
+        self.diagnostics.truncate(diagnostics_len);
+        let (last_position, expected) = (self.last_position, self.expected.clone());
+        let gramspec = self.gramspec;
+        self.position = failure_pos;
+        'scan: while self.position < self.content.len() {
+            let scan_pos = self.position;
+            for expression in &gramspec.recovery[rule_name] {
+                if self.eval(expression)?.is_some() {
+                    break 'scan;
+                }
+                self.position = scan_pos;
+            }
+            self.position += self.content[scan_pos..].chars().next().map_or(1, char::len_utf8);
+        }
+        (self.last_position, self.expected) = (last_position, expected);
+
+        Ok(Some(vec![self.error_node(content_start, failure_pos, &failure_expected)]))
+    }
+
+    /// Returns a missing node for a literal that failed to match where a recovering rule failed,
+    /// so that the rule can be matched again past it.
+    fn missing_node(&mut self, expression: &Expression) -> Option<Node> {
+        let missing_at = self.missing_at?;
+        let (value, text) = match expression {
+            Expression::StringLiteral(string) => (string.value.clone(), string.value.clone()),
+            Expression::Keyword(keyword) => {
+                (keyword.value.clone(), self.gramspec.keyword_value(&keyword.value)?.to_string())
+            }
+            _ => return None,
+        };
+
+        let start_pos = self.position;
+        self.skip_trivia();
+        let position = self.position;
+        self.position = start_pos;
+        if position != missing_at {
+            return None;
+        }
+
+        self.missing_at = None;
+        let (line, column, column_utf16) = self.get_line_col(position);
+        self.diagnostics.push(Diagnostic {
+            message: format!("missing '{}' at line {}, column {}", text, line, column),
+            start_position: position,
+            end_position: position,
+            line,
+            column,
+            column_utf16,
+            expected: vec![format!("'{}'", text.escape_debug())],
+            found: self.content[position..].chars().next().map(|c| c.to_string()),
+        });
+        Some(Node::new_with_position(MISSING_NODE_TYPE, vec![], Some(value), position, position))
+    }
+
+    /// Records a diagnostic for the input from `failure_pos` to the current position, which could
+    /// not be parsed, and returns the node standing in for the input from `start_pos`.
+    fn error_node(&mut self, start_pos: usize, failure_pos: usize, expected: &[String]) -> Node {
+        let (line, column, column_utf16) = self.get_line_col(failure_pos);
+        self.diagnostics.push(Diagnostic {
+            message: self.describe_failure(failure_pos, expected),
+            start_position: failure_pos,
+            end_position: self.position,
+            line,
+            column,
+            column_utf16,
+            expected: expected.to_vec(),
+            found: self.content[failure_pos..].chars().next().map(|c| c.to_string()),
+        });
+        let skipped = self.content[start_pos..self.position].to_string();
+        Node::new_with_position(ERROR_NODE_TYPE, vec![], Some(skipped), start_pos, self.position)
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            let start_pos = self.position;
+            self.ignore_between();
+            if self.position == start_pos {
+                return;
+            }
+        }
+    }
+
+    fn ignore_between(&mut self) {
+        let remaining = &self.content[self.position..];
+        let longest = self
+            .ignore_between
+            .iter()
+            .filter_map(|regex| regex.find(remaining))
+            .map(|found| found.end())
+            .max()
+            .unwrap_or(0);
+        self.position += longest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the input with the grammar, returning the matched text or the first line of the error.
+    fn parse(grammar: &str, input: &str) -> Result<String, String> {
+        let gramspec = crate::load_grammar(grammar).unwrap();
+        match Interpreter::new(&gramspec).parse(input) {
+            Ok(node) => Ok(input[node.start_position..node.get_end_pos()].to_string()),
+            Err(err) => Err(err.to_string().lines().next().unwrap_or_default().to_string()),
+        }
+    }
+
+    #[test]
+    fn cut_commits_the_alternative_it_is_in() {
+        let grammar = "@entry_rule: 'start'\nstart: 'a' ^ >'b' 'b' 'c' | 'a' 'b' 'd'\n";
+        assert_eq!(parse(grammar, "abc"), Ok("abc".to_string()));
+        assert_eq!(parse(grammar, "abd"), Err("expected 'c' but found 'd' at line 1, column 3".to_string()));
+    }
+
+    #[test]
+    fn cut_inside_a_lookahead_does_not_commit_the_alternative_around_it() {
+        let grammar = "@entry_rule: 'start'\nstart: >('a' ^ 'b') 'a' 'b' 'c' | 'a' 'b' 'd'\n";
+        assert_eq!(parse(grammar, "abd"), Ok("abd".to_string()));
+
+        let grammar = "@entry_rule: 'start'\nstart: !('a' ^ 'x') 'a' 'b' 'c' | 'a' 'b' 'd'\n";
+        assert_eq!(parse(grammar, "abd"), Ok("abd".to_string()));
+    }
+
+    #[test]
+    fn cut_inside_an_optional_part_or_repetition_does_not_commit_the_alternative_around_it() {
+        let grammar = "@entry_rule: 'start'\nstart: ('a' ^ 'x')? 'a' 'b' | 'a' 'c'\n";
+        assert_eq!(parse(grammar, "ac"), Ok("ac".to_string()));
+
+        let grammar = "@entry_rule: 'start'\nstart: ('a' ^ 'x')* 'a' 'b' | 'a' 'c'\n";
+        assert_eq!(parse(grammar, "ac"), Ok("ac".to_string()));
+    }
+
+    #[test]
+    fn cut_inside_an_optional_part_commits_the_choice_it_is_in() {
+        let grammar = "@entry_rule: 'start'\nstart: ('a' ^ 'x' | 'a' 'y')? 'a' 'y'\n";
+        assert_eq!(parse(grammar, "axay"), Ok("axay".to_string()));
+        assert_eq!(parse(grammar, "ay"), Ok("ay".to_string()));
+        assert_eq!(parse(grammar, "ayay"), Err("expected end of input but found 'a' at line 1, column 3".to_string()));
+    }
+
+    #[test]
+    fn cut_inside_a_repetition_stops_parsing_when_the_choice_fails() {
+        let grammar = "@entry_rule: 'start'\nstart: ('a' ^ 'x' | 'a' 'y')* 'b'\n";
+        assert_eq!(parse(grammar, "axaxb"), Ok("axaxb".to_string()));
+        assert_eq!(parse(grammar, "axayb"), Err("expected 'x' but found 'y' at line 1, column 4".to_string()));
+    }
+
+    #[test]
+    fn cut_inside_a_negative_lookahead_only_fails_the_lookahead() {
+        let grammar = "@entry_rule: 'start'\nstart: !('a' ^ 'x' | 'a') 'a' 'y'\n";
+        assert_eq!(parse(grammar, "ay"), Ok("ay".to_string()));
+        assert_eq!(parse(grammar, "ax"), Err("unexpected 'a' at line 1, column 1".to_string()));
+    }
+
+    #[test]
+    fn parse_prefix_returns_how_much_input_it_consumed() {
+        let gramspec = crate::load_grammar("@entry_rule: 'start'\n@ignore_between: r' +'\nstart: 'a'+\n").unwrap();
+        let mut interpreter = Interpreter::new(&gramspec);
+        let (node, consumed) = interpreter.parse_prefix("a a b").unwrap();
+        assert_eq!((node.start_position, node.end_position, consumed), (0, 3, 3));
+        assert!(interpreter.parse("a a b").is_err());
+        assert!(interpreter.parse_prefix("b").is_err());
+    }
+}
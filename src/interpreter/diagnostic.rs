@@ -0,0 +1,26 @@
+use std::fmt;
+
+/// A problem in the input that a recovering parse reported and parsed past.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Byte offset where the problem starts.
+    pub start_position: usize,
+    /// Byte offset where the input the problem covers ends.
+    pub end_position: usize,
+    pub line: usize,
+    /// Column of `start_position` in characters.
+    pub column: usize,
+    /// Column of `start_position` in UTF-16 code units, as editors count them.
+    pub column_utf16: usize,
+    /// What was expected at `start_position`, such as `')'`, `r'[0-9]+'` or `end of input`.
+    pub expected: Vec<String>,
+    /// The character found at `start_position`, or None at the end of the input.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
@@ -0,0 +1,85 @@
+/// Node type of the nodes produced by string, regex and keyword matches.
+pub const STRING_NODE_TYPE: &str = "_String";
+/// Node type of the nodes produced by discarded expressions.
+pub const DISCARD_NODE_TYPE: &str = "_Discard";
+/// Node type of the nodes standing in for input skipped by a recovering parse.
+pub const ERROR_NODE_TYPE: &str = "_Error";
+/// Node type of the nodes standing in for a literal a recovering parse found missing.
+pub const MISSING_NODE_TYPE: &str = "_Missing";
+
+/// A node of the tree produced by the interpreter.
+///
+/// Mirrors the `Node` of generated parsers, with the node type stored as the name of the
+/// `NodeType` variant a generated parser would use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub node_type: String,
+    pub children: Vec<Node>,
+    pub value: Option<String>,
+    pub label: Option<String>,
+    pub start_position: usize,
+    pub end_position: usize,
+}
+
+impl Node {
+    pub fn new_with_position(
+        node_type: &str,
+        children: Vec<Node>,
+        value: Option<String>,
+        start_position: usize,
+        end_position: usize,
+    ) -> Self {
+        Node {
+            node_type: node_type.to_string(),
+            children,
+            value,
+            label: None,
+            start_position,
+            end_position,
+        }
+    }
+
+    pub fn formatted(&self, indent: usize, indent_string: &str) -> String {
+        let mut result = String::new();
+        let indent_str = indent_string.repeat(indent);
+        result.push_str(&indent_str);
+        if let Some(label) = &self.label {
+            result.push_str(&format!("{}=", label));
+        }
+        if self.node_type != STRING_NODE_TYPE {
+            result.push_str(&format!("{}: ", self.node_type));
+        }
+        if let Some(val) = &self.value {
+            result.push_str(&format!("\"{}\"", val.escape_debug()));
+        }
+        for child in &self.children {
+            result.push_str(&format!("\n{}", child.formatted(indent + 1, indent_string)));
+        }
+        result
+    }
+
+    /// Returns the first child with the given label.
+    pub fn field(&self, label: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.label.as_deref() == Some(label))
+    }
+
+    /// Returns all children with the given label, in order.
+    pub fn fields(&self, label: &str) -> Vec<&Node> {
+        self.children
+            .iter()
+            .filter(|child| child.label.as_deref() == Some(label))
+            .collect()
+    }
+
+    pub fn pretty_print(&self) {
+        println!("{}", self.formatted(0, "    "));
+    }
+
+    pub fn get_end_pos(&self) -> usize {
+        if let Some(last_child) = self.children.last() {
+            last_child.get_end_pos()
+        } else {
+            self.end_position
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Parser generator for `.grm` grammar specifications.
+//!
+//! The functions in this module cover the usual pipeline: load a grammar, validate it and
+//! generate the source code of a parser for it.
+//!
+//! ```no_run
+//! let gramspec = gramspec::load_grammar_file("grammar.grm")?;
+//! gramspec::validate(&gramspec)?;
+//! let code = gramspec::generate_to_string(&gramspec, "MyParser", gramspec::DEFAULT_INDENT)?;
+//! # Ok::<(), gramspec::Error>(())
+//! ```
+
+pub mod build;
+pub mod error;
+pub mod generator;
+pub mod gramspec_parser;
+pub mod interpreter;
+
+use std::io::Write;
+use std::path::Path;
+
+pub use error::Error;
+pub use generator::Generator;
+pub use generator::templates::Templates;
+pub use gramspec_parser::gramspec::GramSpec;
+pub use gramspec_parser::gramspec::analysis::{Analysis, Terminal};
+pub use gramspec_parser::gramspec::expression::Expression;
+pub use gramspec_parser::parser::Parser;
+pub use interpreter::Interpreter;
+
+/// Name of the generated parser struct when none is given.
+pub const DEFAULT_PARSER_NAME: &str = "GramspecParser";
+/// Indentation used in generated code when none is given.
+pub const DEFAULT_INDENT: &str = "    ";
+
+/// Parses a grammar specification from a string.
+pub fn load_grammar(source: &str) -> Result<GramSpec, Error> {
+    Parser::new(source.to_string())
+        .parse()
+        .map_err(|err| Error::from_boxed(err, Error::Grammar))
+}
+
+/// Reads and parses a grammar specification file, along with any files it includes.
+pub fn load_grammar_file<P: AsRef<Path>>(path: P) -> Result<GramSpec, Error> {
+    Parser::from_file(path)
+        .and_then(|mut parser| parser.parse())
+        .map_err(|err| Error::from_boxed(err, Error::Grammar))
+}
+
+/// Checks that a grammar specification can be turned into a parser. Every problem found is
+/// returned together as `Error::Diagnostics`.
+pub fn validate(gramspec: &GramSpec) -> Result<(), Error> {
+    gramspec
+        .validate()
+        .map_err(|err| Error::from_boxed(err, Error::Validation))
+}
+
+/// Loads the default templates, overridden by any template files found in `directory`.
+pub fn load_templates<P: AsRef<Path>>(directory: P) -> Result<Templates, Error> {
+    Ok(Templates::with_overrides(directory)?)
+}
+
+/// Validates the grammar and returns the source code of its parser.
+///
+/// Rules are generated in the order they are declared, so the same grammar always gives the same
+/// code:
+///
+/// ```
+/// let grammar = "@entry_rule: 'list'\nlist: item, ','+\nitem: number | name\nnumber: r'[0-9]+'\nname: r'[a-z]+'\n";
+/// let first = gramspec::generate_to_string(&gramspec::load_grammar(grammar)?, "ListParser", "    ")?;
+/// let second = gramspec::generate_to_string(&gramspec::load_grammar(grammar)?, "ListParser", "    ")?;
+/// assert_eq!(first, second);
+///
+/// let position = |function: &str| first.find(function).unwrap();
+/// assert!(position("fn _list(") < position("fn _item(") && position("fn _item(") < position("fn _number("));
+/// assert!(position("fn _number(") < position("fn _name("));
+/// # Ok::<(), gramspec::Error>(())
+/// ```
+pub fn generate_to_string(gramspec: &GramSpec, parser_name: &str, indent: &str) -> Result<String, Error> {
+    generate_with_templates(gramspec, Templates::new(), parser_name, indent)
+}
+
+/// Validates the grammar and returns the source code of its parser, generated from the
+/// given templates.
+pub fn generate_with_templates(
+    gramspec: &GramSpec,
+    templates: Templates,
+    parser_name: &str,
+    indent: &str,
+) -> Result<String, Error> {
+    validate(gramspec)?;
+    Generator::with_templates(gramspec, templates)
+        .generate_string(parser_name, indent)
+        .map_err(|err| Error::from_boxed(err, Error::Generation))
+}
+
+/// Validates the grammar and writes the source code of its parser to `writer`.
+pub fn generate_to_writer<W: Write>(
+    gramspec: &GramSpec,
+    mut writer: W,
+    parser_name: &str,
+    indent: &str,
+) -> Result<(), Error> {
+    let code = generate_to_string(gramspec, parser_name, indent)?;
+    writer.write_all(code.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
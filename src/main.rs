@@ -1,36 +1,132 @@
-mod gramspec_parser;
-mod generator;
-mod parser;
+mod cli;
 
-use gramspec_parser::parser::Parser;
-use parser::GramspecParser;
-
-use generator::Generator;
 use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use cli::Command;
+use gramspec::{Analysis, Error, GramSpec, Interpreter, Templates};
 
 fn main() {
-    let generate = false;
-
-    if generate {
-        // Read the grammar specification and code files
-        let gramspec = fs::read_to_string("temp/gramspec.grm").unwrap();
-        // let code = fs::read_to_string("test_files/test.txt").unwrap();
-        // Tokenize the grammar specification
-        let mut parser = Parser::new(gramspec);
-        // Tokenize the input string
-        let gramspec = parser.parse().unwrap_or_else(|err| {
-            eprintln!("Error parsing grammar specification: {}", err);
-            std::process::exit(1);
-        });
-        // Generate the parser code from the grammar specification
-        let generator = Generator::new(gramspec);
-        generator.generate("./src/parser.rs", "GramspecParser", "    ").unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = Command::from_args(&args).unwrap_or_else(|err| {
+        eprintln!("Error: {}\n\n{}", err, cli::USAGE);
+        process::exit(cli::EXIT_USAGE);
+    });
+
+    let exit_code = match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            cli::EXIT_SUCCESS
+        }
+        Command::Check { grammar } => match load_grammar(&grammar) {
+            Ok(_) => {
+                println!("{}: grammar is valid", grammar);
+                cli::EXIT_SUCCESS
+            }
+            Err(code) => code,
+        },
+        Command::Generate { grammar, output, parser_name, indent, templates } => match load_grammar(&grammar) {
+            Ok(gramspec) => match generate(&gramspec, output, &parser_name, &indent, templates) {
+                Ok(()) => cli::EXIT_SUCCESS,
+                Err(err) => {
+                    eprintln!("Error generating parser: {}", err);
+                    exit_code_for(&err)
+                }
+            },
+            Err(code) => code,
+        },
+        Command::Parse { grammar, input, recover } => match load_grammar(&grammar) {
+            Ok(gramspec) => match fs::read_to_string(&input) {
+                Ok(content) => parse(&gramspec, &input, &content, recover),
+                Err(err) => {
+                    eprintln!("Error reading input file '{}': {}", input, err);
+                    cli::EXIT_IO_ERROR
+                }
+            },
+            Err(code) => code,
+        },
+    };
+
+    process::exit(exit_code);
+}
+
+/// Reads, parses and validates a grammar specification, reporting any errors and warnings on
+/// stderr.
+fn load_grammar(path: &str) -> Result<GramSpec, i32> {
+    let report = |err: Error| {
+        match &err {
+            Error::Diagnostics(diagnostics) => {
+                eprintln!("{}\n", diagnostics);
+                let count = diagnostics.error_count();
+                let plural = if count == 1 { "" } else { "s" };
+                eprintln!("Error loading grammar specification '{}': {} error{}", path, count, plural);
+            }
+            _ => eprintln!("Error loading grammar specification '{}': {}", path, err),
+        }
+        exit_code_for(&err)
+    };
+
+    let gramspec = gramspec::load_grammar_file(path).map_err(report)?;
+    gramspec::validate(&gramspec).map_err(report)?;
+    for warning in Analysis::new(&gramspec).nullable_repetitions() {
+        eprintln!("{}\n", warning);
+    }
+    Ok(gramspec)
+}
+
+/// Parses the input with the grammar and prints the tree, along with any errors recovered from.
+fn parse(gramspec: &GramSpec, input: &str, content: &str, recover: bool) -> i32 {
+    let mut interpreter = Interpreter::new(gramspec);
+    let result = if recover {
+        interpreter.parse_with_recovery(content)
     } else {
-        match GramspecParser::new()
-            // .enable_debug()
-            .parse_file("temp/gramspec.grm") {
-                Ok(node) => node.pretty_print(),
-                Err(message)=> eprintln!("Error parsing file: {:?}", message),
+        interpreter.parse(content).map(|node| (node, Vec::new()))
+    };
+
+    match result {
+        Ok((node, diagnostics)) => {
+            node.pretty_print();
+            for diagnostic in &diagnostics {
+                eprintln!("Error parsing '{}': {}", input, diagnostic);
+            }
+            if diagnostics.is_empty() {
+                cli::EXIT_SUCCESS
+            } else {
+                cli::EXIT_INPUT_REJECTED
+            }
         }
+        Err(err) => {
+            eprintln!("Error parsing '{}': {}", input, err);
+            cli::EXIT_INPUT_REJECTED
+        }
+    }
+}
+
+/// Generates the parser code from the grammar specification and writes it to `output` or stdout.
+fn generate(
+    gramspec: &GramSpec,
+    output: Option<String>,
+    parser_name: &str,
+    indent: &str,
+    templates: Option<String>,
+) -> Result<(), Error> {
+    let templates = match templates {
+        Some(directory) => gramspec::load_templates(directory)?,
+        None => Templates::new(),
+    };
+    let code = gramspec::generate_with_templates(gramspec, templates, parser_name, indent)?;
+    match output {
+        Some(output) => fs::write(output, code)?,
+        None => io::stdout().lock().write_all(code.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Maps a library error to the exit code reported for it.
+fn exit_code_for(err: &Error) -> i32 {
+    match err {
+        Error::Io(_) => cli::EXIT_IO_ERROR,
+        Error::Grammar(_) | Error::Diagnostics(_) | Error::Validation(_) | Error::Generation(_) => cli::EXIT_GRAMMAR_ERROR,
     }
 }
@@ -0,0 +1,110 @@
+//! Helpers shared by the integration tests, which parse the same inputs with the interpreter and
+//! with the parsers generated from the grammars in `tests/grammars` into `tests/parsers`.
+//!
+//! After changing a grammar or the generator, regenerate its parser with
+//! `gramspec-parser-generator generate tests/grammars/<name>.grm -o tests/parsers/<name>.rs
+//! --name <Name>Parser`. `generated_parsers_are_up_to_date` fails until it is.
+
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use gramspec::GramSpec;
+use gramspec::Interpreter;
+
+/// The test grammars, with the name of the parser generated from each.
+pub const GRAMMARS: [(&str, &str); 3] =
+    [("calc", "CalcParser"), ("expressions", "ExpressionsParser"), ("statements", "StatementsParser")];
+
+/// Returns the path of a test grammar or of the parser generated from it.
+pub fn path(directory: &str, name: &str, extension: &str) -> String {
+    format!("{}/tests/{}/{}.{}", env!("CARGO_MANIFEST_DIR"), directory, name, extension)
+}
+
+/// Loads and validates a test grammar.
+pub fn load(name: &str) -> GramSpec {
+    let gramspec = gramspec::load_grammar_file(path("grammars", name, "grm")).unwrap();
+    gramspec::validate(&gramspec).unwrap();
+    gramspec
+}
+
+/// A tree built by the interpreter or by a generated parser, whose nodes have the same fields but
+/// different types.
+pub trait Tree {
+    /// The tree as `Node::formatted` renders it, one node per line.
+    fn text(&self) -> String;
+    /// The byte span of every node, in the order `text` lists them.
+    fn spans(&self) -> Vec<(usize, usize)>;
+}
+
+/// Implements `Tree` for a `Node` type.
+#[macro_export]
+macro_rules! impl_tree {
+    ($node:ty) => {
+        impl $crate::common::Tree for $node {
+            fn text(&self) -> String {
+                self.formatted(0, "  ")
+            }
+
+            fn spans(&self) -> Vec<(usize, usize)> {
+                let mut spans = vec![(self.start_position, self.end_position)];
+                for child in &self.children {
+                    spans.extend($crate::common::Tree::spans(child));
+                }
+                spans
+            }
+        }
+    };
+}
+
+impl_tree!(gramspec::interpreter::node::Node);
+
+/// Renders a tree with the span of each node, such as `Number: "1" @4..5`.
+pub fn render(tree: &impl Tree) -> String {
+    let lines: Vec<String> = tree
+        .text()
+        .lines()
+        .zip(tree.spans())
+        .map(|(line, (start, end))| format!("{} @{}..{}", line, start, end))
+        .collect();
+    lines.join("\n")
+}
+
+/// Renders the result of a parse, as the rendered tree or the error message.
+pub fn outcome<T: Tree, E: Display>(result: Result<T, E>) -> Result<String, String> {
+    result.map(|tree| render(&tree)).map_err(|err| err.to_string())
+}
+
+/// Parses the input with the interpreter and checks that the generated parser returned the same
+/// tree or the same error for it. Returns the outcome of both.
+pub fn parse_both<T: Tree, E: Display>(gramspec: &GramSpec, input: &str, generated: Result<T, E>) -> Result<String, String> {
+    let interpreted = outcome(Interpreter::new(gramspec).parse(input));
+    let generated = outcome(generated);
+    assert_eq!(interpreted, generated, "the interpreter and the generated parser disagree on {:?}", input);
+    generated
+}
+
+/// Renders the result of a recovering parse, as the rendered tree followed by its diagnostics, one
+/// per line, or the error message.
+pub fn recovered_outcome<T: Tree, D: Debug, E: Display>(result: Result<(T, Vec<D>), E>) -> Result<String, String> {
+    result
+        .map(|(tree, diagnostics)| {
+            let diagnostics: Vec<String> = diagnostics.iter().map(|diagnostic| format!("{:?}", diagnostic)).collect();
+            format!("{}\n{}", render(&tree), diagnostics.join("\n"))
+        })
+        .map_err(|err| err.to_string())
+}
+
+/// Parses the input with recovery with the interpreter and checks that the generated parser
+/// returned the same tree and diagnostics, or the same error, for it. Returns the outcome of both.
+pub fn recover_both<T: Tree, D: Debug, E: Display>(
+    gramspec: &GramSpec,
+    input: &str,
+    generated: Result<(T, Vec<D>), E>,
+) -> Result<String, String> {
+    let interpreted = recovered_outcome(Interpreter::new(gramspec).parse_with_recovery(input));
+    let generated = recovered_outcome(generated);
+    assert_eq!(interpreted, generated, "the interpreter and the generated parser recover differently from {:?}", input);
+    generated
+}
@@ -0,0 +1,87 @@
+//! Checks the errors of failed parses, with the parser generated from
+//! `tests/grammars/expressions.grm`. The interpreter must report the same errors, though it
+//! returns them boxed rather than as a `ParseError`.
+
+mod common;
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/expressions.rs"]
+mod expressions;
+
+use std::error::Error;
+
+use expressions::{ExpressionsParser, ParseErrorKind};
+use gramspec::Interpreter;
+
+impl_tree!(expressions::node::Node);
+
+/// Parses input that does not match with the generated parser, checking that the interpreter fails
+/// alike, and returns the error with its snippet.
+fn error(input: &str) -> String {
+    let gramspec = common::load("expressions");
+    common::parse_both(&gramspec, input, ExpressionsParser::new().parse(input.to_string())).unwrap_err()
+}
+
+#[test]
+fn errors_list_everything_expected_where_parsing_got_furthest() {
+    assert_eq!(error("f(1 2)"), "expected ',' or ')' but found '2' at line 1, column 5\n1 | f(1 2)\n  |     ^");
+    assert_eq!(
+        error("let x = "),
+        "expected one of r'[0-9]+', r'\"[^\"\\n]*\"' or r'[a-z_]+' but found end of input at line 1, column 9\n1 | let x = \n  |         ^"
+    );
+    assert_eq!(
+        error("{a; b"),
+        "expected one of '(', r'[!?]', ';' or '}' but found end of input at line 1, column 6\n1 | {a; b\n  |      ^"
+    );
+}
+
+#[test]
+fn errors_show_the_line_they_are_on() {
+    assert_eq!(
+        error("let x = 5\n  f(1 2)\n"),
+        "expected ',' or ')' but found '2' at line 2, column 7\n2 |   f(1 2)\n  |       ^"
+    );
+    // Columns, and the caret under them, count characters rather than bytes
+    assert_eq!(error("f(\"é\" 2)"), "expected ',' or ')' but found '2' at line 1, column 7\n1 | f(\"é\" 2)\n  |       ^");
+}
+
+#[test]
+fn errors_can_name_the_rules_that_failed_where_they_start() {
+    let gramspec = common::load("expressions");
+    let input = "let x = }";
+    let generated = ExpressionsParser::new().expect_rule_names().parse(input.to_string()).unwrap_err().to_string();
+    let interpreted = Interpreter::new(&gramspec).expect_rule_names().parse(input).unwrap_err().to_string();
+    assert_eq!(generated, interpreted);
+    assert_eq!(generated, "expected value but found '}' at line 1, column 9\n1 | let x = }\n  |         ^");
+}
+
+#[test]
+fn syntax_errors_locate_what_was_found() {
+    let err = ExpressionsParser::new().parse("let x = 5\n  f(1 2)\n".to_string()).unwrap_err();
+    assert!(matches!(err.kind, ParseErrorKind::Syntax));
+    assert_eq!(err.span, 16..17);
+    assert_eq!((err.line, err.column, err.column_utf16), (2, 7, 7));
+    assert_eq!(err.expected, ["','", "')'"]);
+    assert_eq!(err.found.as_deref(), Some("2"));
+    assert_eq!(err.rule_stack, ["document", "statement", "call", "arguments"]);
+    assert_eq!(err.message(), "expected ',' or ')' but found '2' at line 2, column 7");
+    assert_eq!(err.to_string(), format!("{}\n{}", err.message(), err.snippet));
+
+    // Editors count columns in UTF-16 code units, where some characters take two
+    let err = ExpressionsParser::new().parse("f(\"😀\" 2)".to_string()).unwrap_err();
+    assert_eq!((err.span.start, err.column, err.column_utf16), (9, 7, 8));
+
+    // Nothing is found at the end of the input
+    let err = ExpressionsParser::new().parse("let x = ".to_string()).unwrap_err();
+    assert_eq!((err.span.clone(), err.found.clone()), (8..8, None));
+}
+
+#[test]
+fn unreadable_files_are_io_errors() {
+    let path = common::path("grammars", "missing", "txt");
+    let err = ExpressionsParser::new().parse_file(&path).unwrap_err();
+    assert!(matches!(err.kind, ParseErrorKind::Io(ref io) if io.kind() == std::io::ErrorKind::NotFound));
+    assert!(err.source().is_some());
+    assert_eq!(err.to_string(), err.message());
+}
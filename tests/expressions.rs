@@ -0,0 +1,166 @@
+//! Checks how the constructs of the grammar language match, with the parser generated from
+//! `tests/grammars/expressions.grm` and with the interpreter, which must parse alike.
+
+mod common;
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/expressions.rs"]
+mod expressions;
+
+use expressions::ExpressionsParser;
+use gramspec::Interpreter;
+use expressions::node::{Node, NodeType};
+
+impl_tree!(expressions::node::Node);
+
+/// Parses a document with the generated parser, checking that the interpreter parses it alike.
+/// Errors are returned as their first line.
+fn parse(input: &str) -> Result<Node, String> {
+    let gramspec = common::load("expressions");
+    common::parse_both(&gramspec, input, ExpressionsParser::new().parse(input.to_string()))
+        .map_err(|err| err.lines().next().unwrap_or_default().to_string())?;
+    Ok(ExpressionsParser::new().parse(input.to_string()).unwrap())
+}
+
+/// Returns the first statement of a document, such as its `Flag` or `Call` node.
+fn statement(document: &Node) -> &Node {
+    &document.children[0].children[0]
+}
+
+/// Lists the text matched by each string, regex and keyword in a tree, with its span.
+fn leaves(node: &Node) -> Vec<String> {
+    match &node.value {
+        Some(value) => vec![format!("{}@{}..{}", value, node.start_position, node.end_position)],
+        None => node.children.iter().flat_map(leaves).collect(),
+    }
+}
+
+#[test]
+fn lookaheads_match_without_consuming_input() {
+    // `flag: >('-' ^ '-') '--' name=identifier | '-' name=identifier`
+    let flag = parse("--long").unwrap();
+    assert_eq!(statement(&flag).node_type, NodeType::Flag);
+    assert_eq!(leaves(&flag), ["--@0..2", "long@2..6"]);
+    let flag = parse("-s").unwrap();
+    assert_eq!(leaves(&flag), ["-@0..1", "s@1..2"]);
+
+    // `call: callee=identifier >'(' '(' $arguments ')'`
+    let call = parse("f(x)").unwrap();
+    assert_eq!(statement(&call).node_type, NodeType::Call);
+    assert_eq!(leaves(&call), ["f@0..1", "(@1..2", "x@2..3", ")@3..4"]);
+}
+
+#[test]
+fn negative_lookaheads_reject_what_follows() {
+    // `word: !KW_LET identifier ...`, so a word cannot start with `let`
+    let word = parse("hello").unwrap();
+    assert_eq!(statement(&word).node_type, NodeType::Word);
+    assert_eq!(leaves(&word), ["hello@0..5"]);
+    assert_eq!(parse("letter").unwrap_err(), "expected '=' but found end of input at line 1, column 7");
+}
+
+#[test]
+fn counted_repetitions_match_between_their_bounds() {
+    // `address: octets=r'[0-9]{1,3}', '.'{4}` matches exactly four octets
+    let address = parse("10.0.0.1").unwrap();
+    assert_eq!(statement(&address).fields("octets").len(), 4);
+    assert_eq!(parse("10.0.0").unwrap_err(), "expected '.' but found end of input at line 1, column 7");
+    assert_eq!(parse("10.0.0.1.2").unwrap_err(), "expected '\\n' or end of input but found '.' at line 1, column 9");
+
+    // `color: '0x' r'[0-9a-f]'{6} | '0x' r'[0-9a-f]'{3}`
+    assert_eq!(leaves(&parse("0xa0b0c0").unwrap()).len(), 7);
+    assert_eq!(leaves(&parse("0xabc").unwrap()).len(), 4);
+    assert_eq!(parse("0xabcd").unwrap_err(), "expected r'[0-9a-f]' but found end of input at line 1, column 7");
+
+    // `pause: '.'{3,}` has no maximum
+    assert_eq!(leaves(&parse("...").unwrap()).len(), 3);
+    assert_eq!(leaves(&parse(".....").unwrap()).len(), 5);
+    assert_eq!(parse("..").unwrap_err(), "expected '.' but found end of input at line 1, column 3");
+}
+
+#[test]
+fn counted_repetitions_stop_at_their_maximum() {
+    // `word: ... identifier ~r'[!?]'{0,2}` leaves a third '!' to the rule after it
+    assert_eq!(leaves(&parse("hi!?").unwrap()), ["hi@0..2"]);
+    assert_eq!(parse("hi!!!").unwrap_err(), "expected '\\n' or end of input but found '!' at line 1, column 5");
+}
+
+#[test]
+fn counted_repetitions_need_their_bounds_in_order() {
+    let Err(err) = gramspec::load_grammar("file: 'a'{2,1}\n") else {
+        panic!("a repetition with its bounds out of order was accepted");
+    };
+    assert!(
+        err.to_string().contains("Invalid repetition '{2,1}': the maximum is smaller than the minimum"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn labelled_nodes_are_fields_of_their_parent() {
+    // `assignment: KW_LET ^ name=identifier '=' value=value`
+    let document = parse("let x = \"s\"").unwrap();
+    let assignment = statement(&document);
+    assert_eq!(leaves(assignment.field("name").unwrap()), ["x@4..5"]);
+    assert_eq!(leaves(assignment.field("value").unwrap()), ["\"s\"@8..11"]);
+    assert!(assignment.field("callee").is_none());
+}
+
+#[test]
+fn labels_survive_inlining() {
+    // `call: callee=identifier >'(' '(' $arguments ')'` inlines `arguments: args=value, ','*`
+    let document = parse("f(1, \"s\")").unwrap();
+    let call = statement(&document);
+    assert_eq!(leaves(call.field("callee").unwrap()), ["f@0..1"]);
+    let args: Vec<Vec<String>> = call.fields("args").into_iter().map(leaves).collect();
+    assert_eq!(args, [["1@2..3"], ["\"s\"@5..8"]]);
+    assert!(statement(&parse("f()").unwrap()).fields("args").is_empty());
+
+    // `block: '{' statements '}'` inlines the meta rule `$statements: items=statement, ';'*`
+    let document = parse("{a; f(x)}").unwrap();
+    let items = statement(&document).fields("items");
+    assert_eq!(items.iter().map(|item| &item.children[0].node_type).collect::<Vec<_>>(), [&NodeType::Word, &NodeType::Call]);
+}
+
+#[test]
+fn declared_keywords_match_their_text() {
+    // `KW_LET: 'let'` and `NEWLINE: '\n'` are matched as the keyword they declare
+    assert_eq!(leaves(&parse("let x = 5").unwrap())[0], "KW_LET@0..3");
+    assert_eq!(leaves(&parse("a\nb").unwrap()), ["a@0..1", "NEWLINE@1..2", "b@2..3"]);
+}
+
+#[test]
+fn undeclared_keywords_are_rejected_before_generation() {
+    let grammar = "file: IF expr ENDMARKER\n@precedence expr: r'[a-z]+'\n    prefix: NOT\n";
+    let gramspec = gramspec::load_grammar(grammar).unwrap();
+    let Err(gramspec::Error::Diagnostics(diagnostics)) = gramspec::generate_to_string(&gramspec, "Parser", gramspec::DEFAULT_INDENT) else {
+        panic!("a parser was generated for a grammar with undeclared keywords");
+    };
+    let problems: Vec<String> = diagnostics
+        .diagnostics
+        .iter()
+        .map(|diagnostic| format!("{} at {}:{}", diagnostic.message, diagnostic.line, diagnostic.column))
+        .collect();
+    assert_eq!(problems, ["Keyword 'IF' is not declared at 1:7", "Keyword 'NOT' is not declared at 3:13"]);
+
+    let gramspec = gramspec::load_grammar(&format!("{}IF: 'if'\nNOT: 'not'\n", grammar)).unwrap();
+    assert!(gramspec::generate_to_string(&gramspec, "Parser", gramspec::DEFAULT_INDENT).is_ok());
+}
+
+#[test]
+fn trailing_trivia_is_skipped_before_the_end_of_input() {
+    let gramspec = common::load("expressions");
+    for input in ["x  # done", "x\n# done\n  ", "x\n"] {
+        assert_eq!(leaves(&parse(input).unwrap())[0], "x@0..1");
+        // `ENDMARKER` skips the trivia too, so a prefix parse of the document reaches the end
+        let (_, consumed) = ExpressionsParser::new().parse_prefix(input.to_string()).unwrap();
+        assert_eq!(consumed, input.len());
+        assert_eq!(Interpreter::new(&gramspec).parse_prefix(input).unwrap().1, input.len());
+    }
+    assert_eq!(
+        parse("x y").unwrap_err(),
+        "expected one of '(', r'[!?]', '\\n' or end of input but found 'y' at line 1, column 3"
+    );
+}
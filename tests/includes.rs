@@ -0,0 +1,66 @@
+//! Checks that grammars split across the files in `tests/grammars/include` load as one grammar.
+
+mod common;
+
+use std::fs;
+
+use gramspec::GramSpec;
+use gramspec::Interpreter;
+
+/// Returns the path of a grammar in `tests/grammars/include`.
+fn path(name: &str) -> String {
+    common::path("grammars/include", name, "grm")
+}
+
+/// Returns the canonical path of a grammar in `tests/grammars/include`, as include cycles list it.
+fn canonical(name: &str) -> String {
+    fs::canonicalize(path(name)).unwrap().display().to_string()
+}
+
+/// Loads a grammar, returning its error message if it cannot be loaded.
+fn load(name: &str) -> Result<GramSpec, String> {
+    gramspec::load_grammar_file(path(name)).map_err(|err| err.to_string())
+}
+
+#[test]
+fn included_rules_are_merged_once() {
+    // main.grm includes lexical.grm, and values.grm, which includes lexical.grm again
+    let gramspec = load("main").unwrap();
+    gramspec::validate(&gramspec).unwrap();
+    let names: Vec<&String> = gramspec.in_declaration_order(&gramspec.rules).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["file", "name", "number", "value"]);
+
+    // The ignore_between pattern of the including grammar applies to the included rules
+    let mut interpreter = Interpreter::new(&gramspec);
+    assert!(interpreter.parse("x = 5").is_ok());
+    assert!(interpreter.parse("x = y").is_ok());
+    assert!(interpreter.parse("x = ").is_err());
+}
+
+#[test]
+fn include_cycles_are_reported_at_the_include_that_closes_them() {
+    let Err(err) = load("cycle") else {
+        panic!("an include cycle was accepted");
+    };
+    let expected = format!(
+        "error: Include cycle\n --> {}:1:11\n  |\n1 | @include: 'cycle.grm'\n  |           ^^^^^^^^^^^\n  = note: {} -> {} -> {}",
+        path("cycle_back"),
+        canonical("cycle"),
+        canonical("cycle_back"),
+        canonical("cycle")
+    );
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn definitions_repeated_across_files_name_both_files() {
+    let Err(err) = load("duplicate") else {
+        panic!("a rule defined in an included file and again in the including file was accepted");
+    };
+    let expected = format!(
+        "error: 'name' is defined twice\n --> {}:1:1\n  |\n1 | name: r'[a-z]+'\n  | ^^^^\n  = note: it is first defined at {}:4:1",
+        path("lexical"),
+        path("duplicate")
+    );
+    assert_eq!(err, expected);
+}
@@ -0,0 +1,96 @@
+//! Checks that the interpreter parses like the parsers generated from the same grammars, and that
+//! the generated parsers are up to date with their grammars.
+
+mod common;
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/calc.rs"]
+mod calc;
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/expressions.rs"]
+mod expressions;
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/statements.rs"]
+mod statements;
+
+use std::fs;
+
+use calc::CalcParser;
+use common::parse_both;
+use expressions::ExpressionsParser;
+use statements::StatementsParser;
+
+impl_tree!(calc::node::Node);
+impl_tree!(expressions::node::Node);
+impl_tree!(statements::node::Node);
+
+#[test]
+fn generated_parsers_are_up_to_date() {
+    for (name, parser_name) in common::GRAMMARS {
+        let code = gramspec::generate_to_string(&common::load(name), parser_name, gramspec::DEFAULT_INDENT).unwrap();
+        let path = common::path("parsers", name, "rs");
+        assert!(
+            fs::read_to_string(&path).unwrap() == code,
+            "{} is not the parser generated from {}.grm; regenerate it",
+            path,
+            name
+        );
+    }
+}
+
+#[test]
+fn expressions_parse_alike() {
+    let gramspec = common::load("expressions");
+    let accepted = [
+        // Every kind of statement, with trailing trivia after the last one
+        "let x = 5\nf(1, \"a\", y)\n10.0.0.1\n0xabc\n0xa0b0c0\n....\n--long\n-s\nhello!?\n{a; b!}\n# done\n  ",
+        "\n\nword\n\n",
+    ];
+    let rejected = ["let = 5", "letter", "f(1,)", "10.0.0", "0xabcd", "..", "hello!!!", "{a; b", "a\n\n  b c"];
+    for (inputs, accept) in [(&accepted[..], true), (&rejected[..], false)] {
+        for input in inputs {
+            let result = parse_both(&gramspec, input, ExpressionsParser::new().parse(input.to_string()));
+            assert_eq!(result.is_ok(), accept, "{:?}: {:?}", input, result);
+        }
+    }
+}
+
+#[test]
+fn errors_are_reported_alike() {
+    let gramspec = common::load("expressions");
+    let input = "let x = 5\nf(1,)";
+    let result = parse_both(&gramspec, input, ExpressionsParser::new().parse(input.to_string()));
+    assert_eq!(
+        result,
+        Err("expected one of r'[0-9]+', r'\"[^\"\\n]*\"' or r'[a-z_]+' but found ')' at line 2, column 5\n2 | f(1,)\n  |     ^".to_string())
+    );
+}
+
+#[test]
+fn precedence_rules_parse_alike() {
+    let gramspec = common::load("calc");
+    let accepted = ["1", "1 + 2 * 3 - 4", "2 ** 3 ** 2", "-1 + -(2)!", "1 < 2 + 3", "(1 + 2) * 3!!"];
+    let rejected = ["1 < 2 < 3", "1 +", "* 2", "(1"];
+    for (inputs, accept) in [(&accepted[..], true), (&rejected[..], false)] {
+        for input in inputs {
+            let result = parse_both(&gramspec, input, CalcParser::new().parse(input.to_string()));
+            assert_eq!(result.is_ok(), accept, "{:?}: {:?}", input, result);
+        }
+    }
+}
+
+#[test]
+fn statements_parse_alike() {
+    let gramspec = common::load("statements");
+    let accepted = ["let a = 1; print a; { print 2; }", ""];
+    let rejected = ["let a = ;", "print 1 print 2;", "{ let a = 1;"];
+    for (inputs, accept) in [(&accepted[..], true), (&rejected[..], false)] {
+        for input in inputs {
+            let result = parse_both(&gramspec, input, StatementsParser::new().parse(input.to_string()));
+            assert_eq!(result.is_ok(), accept, "{:?}: {:?}", input, result);
+        }
+    }
+}
@@ -0,0 +1,1784 @@
+use std::error::Error;
+use std::collections::HashMap;
+use regex::Regex;
+
+use expression::Expression::{self, *};
+use expression::{Associativity, Operator};
+use node::{Node, NodeType};
+
+const KEYWORDS: &[(&str, &str)] = &[
+    ("KW_LET", "let"),
+    ("NEWLINE", "\n"),
+];
+
+#[allow(dead_code)]
+pub struct ExpressionsParser {
+    pub position: usize,
+    pub debug: bool,
+
+    debug_indent_level: usize,
+    content: std::string::String,
+    memos: HashMap<usize, HashMap<std::string::String, Box<Option<Vec<Node>>>>>,
+    /// Regexes of the grammar, compiled the first time they are matched.
+    regexes: HashMap<&'static str, Regex>,
+
+    expected: Vec<std::string::String>,
+    last_position: usize,
+    last_rule_stack: Vec<std::string::String>,
+    rule_stack: Vec<std::string::String>,
+    expect_rule_names: bool,
+    cut: bool,
+    /// Whether a lookahead or an optional part is being matched, which a failure after a cut
+    /// inside it only fails.
+    cut_scoped: bool,
+
+    recovering: bool,
+    missing_at: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem in the input that a recovering parse reported and parsed past.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: std::string::String,
+    pub start_position: usize,
+    pub end_position: usize,
+    pub line: usize,
+    pub column: usize,
+    /// The column in UTF-16 code units, as editors count them.
+    pub column_utf16: usize,
+    /// What was expected where the problem starts, such as `')'`, `r'[0-9]+'` or `end of input`.
+    pub expected: Vec<std::string::String>,
+    /// The character found where the problem starts, or None at the end of the input.
+    pub found: Option<std::string::String>,
+}
+
+/// Why parsing failed.
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// The input does not match the grammar.
+    Syntax,
+    /// The input file could not be read.
+    Io(std::io::Error),
+    /// A rule or keyword was used that the grammar does not define, or a regex does not compile.
+    Internal(std::string::String),
+}
+
+/// An error returned by the parser. Syntax errors are located at the furthest position the input
+/// matched up to, where every expression that was tried there failed.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Byte offsets of the text found where parsing failed.
+    pub span: std::ops::Range<usize>,
+    pub line: usize,
+    /// The column in characters.
+    pub column: usize,
+    /// The column in UTF-16 code units, as editors count them.
+    pub column_utf16: usize,
+    /// What was expected where parsing failed, such as `')'`, `r'[0-9]+'` or `end of input`.
+    pub expected: Vec<std::string::String>,
+    /// The character found where parsing failed, or None at the end of the input.
+    pub found: Option<std::string::String>,
+    /// The rules being matched where parsing failed, from the entry rule inwards.
+    pub rule_stack: Vec<std::string::String>,
+    /// The line of the input where parsing failed, with a caret under the column.
+    pub snippet: std::string::String,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind) -> Self {
+        ParseError { kind, span: 0..0, line: 0, column: 0, column_utf16: 0, expected: Vec::new(), found: None, rule_stack: Vec::new(), snippet: std::string::String::new() }
+    }
+
+    fn internal(message: std::string::String) -> Self {
+        ParseError::new(ParseErrorKind::Internal(message))
+    }
+
+    /// Describes the error in one line, such as "expected ')' or ',' but found 'x' at line 3,
+    /// column 14", without the snippet.
+    pub fn message(&self) -> std::string::String {
+        match &self.kind {
+            ParseErrorKind::Syntax => {}
+            ParseErrorKind::Io(error) => return error.to_string(),
+            ParseErrorKind::Internal(message) => return message.clone(),
+        }
+        let found = match &self.found {
+            Some(found) => format!("'{}'", found.escape_debug()),
+            None => "end of input".to_string(),
+        };
+        let expected = match self.expected.as_slice() {
+            [] => return format!("unexpected {} at line {}, column {}", found, self.line, self.column),
+            [single] => single.clone(),
+            [first, second] => format!("{} or {}", first, second),
+            [rest @ .., last] => format!("one of {} or {}", rest.join(", "), last),
+        };
+        format!("expected {} but found {} at line {}, column {}", expected, found, self.line, self.column)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ParseErrorKind::Syntax => write!(f, "{}\n{}", self.message(), self.snippet),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(error: std::io::Error) -> Self {
+        ParseError::new(ParseErrorKind::Io(error))
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[allow(dead_code)]
+impl ExpressionsParser {
+    pub fn new() -> Self {
+        ExpressionsParser { content: std::string::String::new(), position: 0, memos: HashMap::new(), regexes: HashMap::new(), debug: false, debug_indent_level: 0, expected: Vec::new(), last_position: 0, last_rule_stack: Vec::new(), rule_stack: Vec::new(), expect_rule_names: false, cut: false, cut_scoped: false, recovering: false, missing_at: None, diagnostics: Vec::new() }
+    }
+
+    pub fn enable_debug(mut self) -> Self {
+        self.debug = true;
+        self
+    }
+
+    /// Reports rules that fail where they start by their name in error messages, such as
+    /// "expected statement", instead of by the literals they start with.
+    pub fn expect_rule_names(mut self) -> Self {
+        self.expect_rule_names = true;
+        self
+    }
+
+    fn debug_log(&self, message: &str) {
+        if self.debug {
+            println!("{}DEBUG: \x1b[34m{}\x1b[0m | Position: {} | Next chars: {:?}",
+                "    ".repeat(self.debug_indent_level),
+                message,
+                self.position,
+                self.content[self.position..].chars().take(20).collect::<std::string::String>()
+            );
+            let mut input = std::string::String::new();
+            std::io::stdin().read_line(&mut input).unwrap();
+        }
+    }
+
+    /// Returns the line of a byte offset in the input, and its column both in characters and in
+    /// UTF-16 code units, all counting from 1.
+    fn get_line_col(&self, position: usize) -> (usize, usize, usize) {
+        let before = &self.content[..position];
+        let line = before.matches('\n').count() + 1;
+        let line_text = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        (line, line_text.chars().count() + 1, line_text.encode_utf16().count() + 1)
+    }
+
+    fn pass(&self) {
+        if self.debug {
+            println!("{}\x1b[32mPASS\x1b[0m\n", "    ".repeat(self.debug_indent_level));
+        }
+    }
+
+    fn fail(&self) {
+        if self.debug {
+            println!("{}\x1b[31mFAIL\x1b[0m\n", "    ".repeat(self.debug_indent_level));
+        }
+    }
+
+    pub fn parse(&mut self, input: std::string::String) -> Result<Node, ParseError> {
+        self.reset(input);
+
+        if let Some(nodes) = self._document()? {
+            // Trailing trivia, such as a final newline, does not have to be matched by the grammar
+            self.__skip_trivia()?;
+            if self.position == self.content.len() {
+                let nodes = self.clear_discard_nodes(nodes);
+                return Ok(nodes[0].clone());
+            }
+            self.record_failure(|| "end of input".to_string());
+        }
+        Err(self.parse_error())
+    }
+
+    /// Parses the start of the input with the entry rule, without requiring the whole input to
+    /// match. Returns the tree and the number of bytes it consumed.
+    pub fn parse_prefix(&mut self, input: std::string::String) -> Result<(Node, usize), ParseError> {
+        self.reset(input);
+
+        if let Some(nodes) = self._document()? {
+            let nodes = self.clear_discard_nodes(nodes);
+            return Ok((nodes[0].clone(), self.position));
+        }
+        Err(self.parse_error())
+    }
+
+    /// Parses the input like `parse`, but recovers from errors in the rules declared with `@recover`.
+    /// Returns the tree, with error and missing nodes where the input could not be parsed, and the
+    /// diagnostics describing them.
+    pub fn parse_with_recovery(&mut self, input: std::string::String) -> Result<(Node, Vec<Diagnostic>), ParseError> {
+        // Recovery can change which alternatives match, so it is only used when the input does not parse
+        if let Ok(node) = self.parse(input.clone()) {
+            return Ok((node, vec![]));
+        }
+
+        self.reset(input);
+        self.recovering = true;
+        let result = self.parse_recovering();
+        self.recovering = false;
+        result
+    }
+
+    fn parse_recovering(&mut self) -> Result<(Node, Vec<Diagnostic>), ParseError> {
+        let nodes = match self._document()? {
+            Some(nodes) => nodes,
+            None => return Err(self.parse_error()),
+        };
+        let mut root = self.clear_discard_nodes(nodes)[0].clone();
+
+        // Input after the end of the tree is kept in a final error node
+        self.__skip_trivia()?;
+        if self.position < self.content.len() {
+            let start_pos = self.position;
+            self.record_failure(|| "end of input".to_string());
+            let (failure_pos, expected) = (self.last_position, self.expected.clone());
+            self.position = self.content.len();
+            let error = self.error_node(start_pos, failure_pos, &expected);
+            root.append(error);
+        }
+        Ok((root, std::mem::take(&mut self.diagnostics)))
+    }
+
+    fn reset(&mut self, input: std::string::String) {
+        self.position = 0;
+        self.content = input;
+        self.memos.clear();
+        self.expected.clear();
+        self.last_position = 0;
+        self.last_rule_stack.clear();
+        self.rule_stack = vec!["document".to_string()];
+        self.cut = false;
+        self.cut_scoped = false;
+        self.missing_at = None;
+        self.diagnostics.clear();
+    }
+
+    /// Records that `expected` failed to match at the current position, if no expression failed
+    /// further into the input.
+    fn record_failure(&mut self, expected: impl FnOnce() -> std::string::String) {
+        if self.position > self.last_position {
+            self.last_position = self.position;
+            self.expected.clear();
+        }
+        if self.position == self.last_position {
+            if self.expected.is_empty() {
+                self.last_rule_stack = self.rule_stack.clone();
+            }
+            let expected = expected();
+            if !self.expected.contains(&expected) {
+                self.expected.push(expected);
+            }
+        }
+    }
+
+    /// Restores the furthest failure saved before a nested attempt, unless the attempt failed
+    /// further into the input. Failures at the same position are combined.
+    fn restore_failure(&mut self, position: usize, expected: Vec<std::string::String>, rule_stack: Vec<std::string::String>) {
+        if position > self.last_position || (position == self.last_position && self.expected.is_empty()) {
+            self.last_position = position;
+            self.expected = expected;
+            self.last_rule_stack = rule_stack;
+        } else if position == self.last_position {
+            let attempted = std::mem::replace(&mut self.expected, expected);
+            for item in attempted {
+                if !self.expected.contains(&item) {
+                    self.expected.push(item);
+                }
+            }
+        }
+    }
+
+    fn parse_error(&self) -> ParseError {
+        self.syntax_error(self.last_position, &self.expected, &self.last_rule_stack)
+    }
+
+    /// Returns a syntax error for a failure at `position`, where `expected` was expected.
+    fn syntax_error(&self, position: usize, expected: &[std::string::String], rule_stack: &[std::string::String]) -> ParseError {
+        let found = self.content[position..].chars().next();
+        let (line, column, column_utf16) = self.get_line_col(position);
+        ParseError {
+            kind: ParseErrorKind::Syntax,
+            span: position..position + found.map_or(0, char::len_utf8),
+            line,
+            column,
+            column_utf16,
+            expected: expected.to_vec(),
+            found: found.map(|c| c.to_string()),
+            rule_stack: rule_stack.to_vec(),
+            snippet: self.snippet(position),
+        }
+    }
+
+    /// Returns the line of the input containing `position`, with a caret under its column.
+    fn snippet(&self, position: usize) -> std::string::String {
+        let (line, col, _) = self.get_line_col(position);
+        let text = self.content.lines().nth(line - 1).unwrap_or("");
+        let number = line.to_string();
+        format!("{} | {}\n{} | {}^", number, text, " ".repeat(number.len()), " ".repeat(col - 1))
+    }
+
+    pub fn parse_file(&mut self, file_path: &str) -> Result<Node, ParseError> {
+        let content = std::fs::read_to_string(file_path)?;
+        self.parse(content)
+    }
+
+    fn clear_discard_nodes(&self, nodes: Vec<Node>) -> Vec<Node> {
+        nodes.into_iter()
+            .filter(|node| node.node_type != NodeType::_Discard)
+            .map(|mut node| {
+                node.children = self.clear_discard_nodes(node.children);
+                node
+            })
+            .collect()
+    }
+
+    fn circular_wrapper(&mut self, rule_name: std::string::String) -> Result<Option<Vec<Node>>, ParseError> {
+        let pos = self.position;
+
+        if let Some(cached_result_box) = self.memos.get(&pos).and_then(|memo| memo.get(&rule_name)) {
+            let cached_result = *cached_result_box.clone();
+
+            let end_pos = cached_result.as_ref()
+                .and_then(|nodes| nodes.iter().map(|n| n.get_end_pos()).max())
+                .unwrap_or(pos);
+            self.position = end_pos;
+
+            return Ok(cached_result);
+        }
+
+        self.memos.entry(pos).or_insert_with(HashMap::new).insert(rule_name.clone(), Box::new(None));
+
+        let mut last_result = None;
+        let mut last_pos = pos;
+
+        loop {
+            self.position = pos;
+
+            let result = self.call_rule(&rule_name, false)?;
+            self.debug_indent_level -= 1;
+            let end_pos = self.position;
+
+            if end_pos <= last_pos {
+                break;
+            }
+
+            last_result = result;
+            last_pos = end_pos;
+
+            if let Some(memo) = self.memos.get_mut(&pos) {
+                memo.insert(rule_name.clone(), Box::new(last_result.clone()));
+            }
+        }
+
+        // If the result was a failure, remove it from the cache to prevent poisoning
+        if last_result.is_none() {
+            if let Some(memo) = self.memos.get_mut(&pos) {
+                memo.remove(&rule_name);
+            }
+        }
+
+        self.position = last_pos;
+        Ok(last_result)
+    }
+
+    fn expect_string(&mut self, string: &str) -> Result<Option<Vec<Node>>, ParseError> {
+        self.debug_log(&format!("Expect string: '{}'", string));
+        let initial_pos = self.position;
+        let mut start_pos = self.position;
+        loop {
+            if self.content[self.position..].starts_with(string) {
+                self.position += string.len();
+                self.pass();
+                return Ok(Some(vec![Node::new_with_position(NodeType::_String, vec![], Some(string.to_string()), start_pos, self.position)]));
+            } else {
+                self.__ignore_between()?;
+                if start_pos == self.position {
+                    break;
+                }
+
+                start_pos = self.position;
+            }
+        }
+        self.record_failure(|| format!("'{}'", string.escape_debug()));
+        self.position = initial_pos;
+        self.fail();
+        Ok(None)
+    }
+
+    /// Compiles a regex of the grammar unless it was compiled before.
+    fn compile_regex(&mut self, regex: &'static str) -> Result<(), ParseError> {
+        if !self.regexes.contains_key(regex) {
+            let compiled = Regex::new(regex).map_err(|err| ParseError::internal(format!("Invalid regex '{}': {}", regex, err)))?;
+            self.regexes.insert(regex, compiled);
+        }
+        Ok(())
+    }
+
+    fn expect_regex(&mut self, regex: &'static str) -> Result<Option<Vec<Node>>, ParseError> {
+        self.debug_log(&format!("Expect regex: '{}'", regex));
+        self.compile_regex(regex)?;
+        let initial_pos = self.position;
+        let mut start_pos = self.position;
+        loop {
+            if let Some(captures) = self.regexes[regex].captures(&self.content[self.position..]) {
+                self.position += captures.get(0).unwrap().end();
+                self.pass();
+                return Ok(Some(vec![Node::new_with_position(
+                    NodeType::_String,
+                    vec![],
+                    Some(captures.get(0).unwrap().as_str().to_string()),
+                    start_pos,
+                    self.position
+                )]));
+            } else {
+                self.__ignore_between()?;
+                if start_pos == self.position {
+                    break;
+                }
+
+                start_pos = self.position;
+            }
+        }
+        // Regexes are anchored to the start of the remaining input, which is left out of messages
+        let pattern = regex.strip_prefix("^(?:").and_then(|pattern| pattern.strip_suffix(')')).unwrap_or(regex);
+        self.record_failure(|| format!("r'{}'", pattern));
+        self.position = initial_pos;
+        self.fail();
+        Ok(None)
+    }
+
+    fn get_keywords_map(&self) -> HashMap<std::string::String, std::string::String> {
+        KEYWORDS.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<Option<Vec<Node>>, ParseError> {
+        let keyword_value = self.get_keywords_map().get(keyword)
+            .ok_or_else(|| ParseError::internal(format!("Unknown keyword: {}", keyword)))?
+            .to_owned();
+        // Keywords match like strings, but their nodes hold the keyword name
+        let nodes = self.expect_string(&keyword_value)?;
+        Ok(nodes.map(|nodes| nodes.into_iter().map(|mut node| {
+            node.value = Some(keyword.to_string());
+            node
+        }).collect()))
+    }
+
+    /// Evaluates an expression. A cut inside a lookahead, an optional part or a repetition only
+    /// commits to the choices inside it, not to the alternative around it. A failure after a cut
+    /// inside a lookahead or an optional part only fails that part, while inside a repetition it
+    /// stops parsing, as it does outside of it.
+    fn eval(&mut self, expression: &Expression) -> Result<Option<Vec<Node>>, ParseError> {
+        let scopes_cut = matches!(
+            expression,
+            Expression::Lookahead(_)
+                | Expression::NotLookahead(_)
+                | Expression::Optional(_)
+                | Expression::RepeatZero(_)
+                | Expression::RepeatOne(_)
+                | Expression::RepeatRange(..)
+                | Expression::DelimitRepeatZero(..)
+                | Expression::DelimitRepeatOne(..)
+                | Expression::DelimitRepeatRange(..)
+        );
+        if !scopes_cut {
+            return self.eval_expression(expression);
+        }
+        let outer_cut = std::mem::replace(&mut self.cut, false);
+        let outer_cut_scoped = self.cut_scoped;
+        self.cut_scoped |= matches!(expression, Expression::Lookahead(_) | Expression::NotLookahead(_) | Expression::Optional(_));
+        let result = self.eval_expression(expression);
+        self.cut = outer_cut;
+        self.cut_scoped = outer_cut_scoped;
+        result
+    }
+
+    fn eval_expression(&mut self, expression: &Expression) -> Result<Option<Vec<Node>>, ParseError> {
+        match expression {
+            Expression::Rule(rule) => {
+                if self.recovering && self.recovery_expressions(rule).is_some() {
+                    return self.call_rule_recovering(rule);
+                }
+                if self.expect_rule_names {
+                    return self.call_rule_expecting_name(rule);
+                }
+                if let Some(nodes) = self.call_rule(rule, true)? {
+                    self.debug_indent_level -= 1;
+                    Ok(Some(nodes))
+                } else {
+                    self.debug_indent_level -= 1;
+                    Ok(None)
+                }
+            },
+            Expression::RegexLiteral(regex) => self.expect_regex(*regex),
+            Expression::StringLiteral(string) => self.expect_string(string),
+            Expression::Keyword(keyword) => self.expect_keyword(keyword),
+            Expression::Cut => {
+                // Commit to the current alternative
+                self.cut = true;
+                Ok(Some(vec![]))
+            },
+            Expression::EndOfInput => {
+                self.debug_log("Expect end of input");
+                let start_pos = self.position;
+                self.__skip_trivia()?;
+                if self.position == self.content.len() {
+                    self.pass();
+                    return Ok(Some(vec![]));
+                }
+                self.record_failure(|| "end of input".to_string());
+                self.position = start_pos;
+                self.fail();
+                Ok(None)
+            },
+            Expression::Or(left, right) => {
+                let start_pos = self.position;
+                let (left_nodes, left_committed) = self.eval_alternative(&*left)?;
+                if left_committed {
+                    return Ok(left_nodes);
+                }
+                let left_end = self.position;
+                self.position = start_pos;
+                let (right_nodes, right_committed) = self.eval_alternative(&*right)?;
+                let right_end = self.position;
+
+                if left_nodes.is_some() && !right_committed && (right_nodes.is_none() || left_end >= right_end) {
+                    self.position = left_end;
+                    return Ok(left_nodes);
+                } else if right_nodes.is_some() {
+                    self.position = right_end;
+                    return Ok(right_nodes);
+                } else {
+                    self.position = start_pos;
+                    return Ok(None);
+                }
+            },
+            Expression::And(left, right) => {
+                let start_pos = self.position;
+                let left_nodes = self.eval(&*left)?;
+                if left_nodes.is_none() {
+                    return Ok(None);
+                }
+                let mut right_nodes = self.eval(&*right)?;
+                if right_nodes.is_none() {
+                    right_nodes = self.missing_node(&*right)?.map(|missing| vec![missing]);
+                }
+                if right_nodes.is_none() {
+                    self.position = start_pos;
+                    return Ok(None);
+                }
+                let mut final_nodes = left_nodes.unwrap();
+                final_nodes.extend(right_nodes.unwrap());
+                Ok(Some(final_nodes))
+            },
+            Expression::DelimitRepeatOne(expression, delimiter) => {
+                // Attempt to parse the first expression
+                let nodes = self.eval(&*expression)?;
+                // If the first expression fails, return an empty vector
+                if nodes.is_none() {
+                    return Ok(None);
+                }
+
+                let mut nodes = nodes.unwrap();
+
+                // Attempt to parse subsequent expressions with delimiters
+                loop {
+                    let start = self.position;
+                    // Attempt to parse the delimiter
+                    let delimiter_nodes = self.eval(&*delimiter)?;
+                    // If it fails, break the loop
+                    if delimiter_nodes.is_none() {
+                        self.position = start; // Technically unnecessary as a failure would leave position unchanged, but just to be consistent
+                        break;
+                    }
+                    // Attempt to parse the next expression
+                    let expression_nodes = self.eval(&*expression)?;
+                    // If the next expression fails, break the loop
+                    if expression_nodes.is_none() {
+                        self.position = start;
+                        break;
+                    }
+
+                    // Only if both delimiter and expression are successful, append them to the nodes
+                    nodes.extend(delimiter_nodes.unwrap());
+                    nodes.extend(expression_nodes.unwrap());
+
+                    // Prevent infinite loops by checking if position has advanced
+                    if self.position <= start {
+                        break;
+                    }
+                }
+
+                // Return the nodes collected so far
+                Ok(Some(nodes))
+            },
+            Expression::DelimitRepeatZero(left, right) => {
+                // Attempt to parse the first expression
+                let nodes = self.eval(&*left)?;
+                // If the first expression fails, return an empty vector
+                if nodes.is_none() {
+                    return Ok(Some(vec![]));
+                }
+
+                let mut nodes = nodes.unwrap();
+
+                // Attempt to parse subsequent expressions with delimiters
+                loop {
+                    let start = self.position;
+                    // Attempt to parse the delimiter
+                    let delimiter_nodes = self.eval(&*right)?;
+                    // If it fails, break the loop
+                    if delimiter_nodes.is_none() {
+                        self.position = start; // Technically unnecessary as a failure would leave position unchanged, but just to be consistent
+                        break;
+                    }
+                    // Attempt to parse the next expression
+                    let expression_nodes = self.eval(&*left)?;
+                    // If the next expression fails, break the loop
+                    if expression_nodes.is_none() {
+                        self.position = start;
+                        break;
+                    }
+
+                    // Only if both delimiter and expression are successful, append them to the nodes
+                    nodes.extend(delimiter_nodes.unwrap());
+                    nodes.extend(expression_nodes.unwrap());
+
+                    // Prevent infinite loops by checking if position has advanced
+                    if self.position <= start {
+                        break;
+                    }
+                }
+
+                // Return the nodes collected so far
+                Ok(Some(nodes))
+            },
+            Expression::RepeatOne(expr) => {
+                let mut nodes = self.eval(&*expr)?;
+                if nodes.is_none() { return Ok(None); }
+
+                let mut last_pos = self.position;
+                while let Some(new_nodes) = self.eval(&*expr)? {
+                    nodes.as_mut().unwrap().extend(new_nodes);
+                    if self.position == last_pos {
+                        break;
+                    }
+                    last_pos = self.position;
+                }
+
+                Ok(nodes)
+            },
+            Expression::RepeatZero(expr) => {
+                let mut nodes = self.eval(&*expr)?;
+                if nodes.is_none() { return Ok(Some(vec![])); }
+
+                let mut last_pos = self.position;
+                while let Some(new_nodes) = self.eval(&*expr)? {
+                    nodes.as_mut().unwrap().extend(new_nodes);
+                    if self.position == last_pos {
+                        break;
+                    }
+                    last_pos = self.position;
+                }
+
+                Ok(nodes)
+            },
+            Expression::Optional(expr) => {
+                let mut nodes = self.eval(&*expr)?;
+
+                if nodes.is_none() {
+                    nodes = Some(vec![]);
+                }
+
+                Ok(nodes)
+            },
+            Expression::RepeatRange(expr, min, max) => {
+                self.eval_repeat_range(&*expr, None, *min, *max)
+            },
+            Expression::DelimitRepeatRange(expression, delimiter, min, max) => {
+                self.eval_repeat_range(&*expression, Some(&*delimiter), *min, *max)
+            },
+            Expression::Label(label, expr) => {
+                let nodes = self.eval(&*expr)?;
+                if nodes.is_none() {
+                    return Ok(None);
+                }
+                let mut nodes = nodes.unwrap();
+                // Labels given inside the expression, such as those of an inlined meta rule, are kept
+                for node in nodes.iter_mut() {
+                    if node.label.is_none() {
+                        node.label = Some(label.to_string());
+                    }
+                }
+                Ok(Some(nodes))
+            },
+            Expression::Discard(expr) => {
+                let start_pos = self.position;
+                let nodes = self.eval(&*expr)?;
+                if nodes.is_none() {
+                    return Ok(None);
+                }
+                let nodes = nodes.unwrap();
+                if nodes.len() == 0 {
+                    return Ok(Some(vec![]));
+                }
+                let last_node  = nodes[nodes.len() - 1].clone();
+                let node = Node::new_with_position(NodeType::_Discard, vec![], None, start_pos, last_node.get_end_pos());
+                Ok(Some(vec![node]))
+            }
+            Expression::Meta(expr) => {
+                let nodes = self.eval(&*expr)?;
+                if nodes.is_none() {
+                    return Ok(None);
+                }
+                // Inline the children of the matched rule nodes into the parent
+                let nodes = nodes.unwrap().into_iter().flat_map(|node| node.children).collect();
+                Ok(Some(nodes))
+            }
+            Expression::Lookahead(expr) => {
+                // Match without consuming any input
+                let start_pos = self.position;
+                let nodes = self.eval(&*expr)?;
+                self.position = start_pos;
+                if nodes.is_none() {
+                    return Ok(None);
+                }
+                Ok(Some(vec![]))
+            }
+            Expression::NotLookahead(expr) => {
+                // Succeed without consuming any input only if the expression does not match. What the
+                // expression fails to match was not expected, so its failures are not recorded
+                let start_pos = self.position;
+                let failure = (self.last_position, self.expected.clone(), self.last_rule_stack.clone());
+                let nodes = self.eval(&*expr)?;
+                self.position = start_pos;
+                (self.last_position, self.expected, self.last_rule_stack) = failure;
+                if nodes.is_some() {
+                    return Ok(None);
+                }
+                Ok(Some(vec![]))
+            }
+        }
+    }
+
+    fn eval_repeat_range(&mut self, expression: &Expression, delimiter: Option<&Expression>, min: usize, max: Option<usize>) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let mut nodes = vec![];
+        let mut count = 0;
+        // Stop after the maximum number of repetitions, leaving any further input to the rest of the rule
+        while max.map_or(true, |max| count < max) {
+            let start = self.position;
+            let mut new_nodes = vec![];
+            if count > 0 {
+                if let Some(delimiter) = delimiter {
+                    let delimiter_nodes = self.eval(delimiter)?;
+                    if delimiter_nodes.is_none() {
+                        break;
+                    }
+                    new_nodes = delimiter_nodes.unwrap();
+                }
+            }
+            let expression_nodes = self.eval(expression)?;
+            if expression_nodes.is_none() {
+                self.position = start;
+                break;
+            }
+
+            new_nodes.extend(expression_nodes.unwrap());
+            nodes.extend(new_nodes);
+            count += 1;
+
+            // An expression that matches without consuming input would match every remaining time
+            if self.position == start {
+                count = count.max(min);
+                break;
+            }
+        }
+
+        if count < min {
+            self.position = start_pos;
+            return Ok(None);
+        }
+        Ok(Some(nodes))
+    }
+
+    /// Evaluates one alternative of a choice, returning whether a cut committed to it. If the
+    /// alternative fails after a cut, parsing stops with an error instead of backtracking, unless
+    /// the choice is inside a lookahead or an optional part, which fails instead.
+    fn eval_alternative(&mut self, expression: &Expression) -> Result<(Option<Vec<Node>>, bool), ParseError> {
+        let outer_cut = self.cut;
+        self.cut = false;
+        let result = self.eval(expression);
+        let committed = self.cut;
+        self.cut = outer_cut;
+
+        let nodes = result?;
+        // While recovering, the failure is left to the enclosing recovering rule
+        if committed && nodes.is_none() && !self.recovering && !self.cut_scoped {
+            return Err(self.parse_error());
+        }
+        Ok((nodes, committed))
+    }
+
+    /// Parses an operand followed by the operators binding at least as tightly as `min_level`, by
+    /// precedence climbing. Returns the nodes and whether an operator was applied, in which case
+    /// they are a single node of `node_type` holding the operands and the operator.
+    fn eval_precedence(&mut self, operand: &[Expression], operators: &[Operator], min_level: usize, node_type: node::NodeType) -> Result<Option<(Vec<Node>, bool)>, ParseError> {
+        let start_pos = self.position;
+        let (mut nodes, mut applied) = match self.match_operator(operators, true)? {
+            Some((operator, operator_nodes)) => {
+                let Some((operand_nodes, _)) = self.eval_precedence(operand, operators, operator.level, node_type.clone())? else {
+                    self.position = start_pos;
+                    return Ok(None);
+                };
+                (vec![self.operator_node(node_type.clone(), vec![("operator", operator_nodes), ("operand", operand_nodes)], start_pos)], true)
+            }
+            None => match self.get_longest_expression_match(operand)? {
+                Some(nodes) => (nodes, false),
+                None => return Ok(None),
+            },
+        };
+
+        let mut nonassoc_level = None;
+        loop {
+            let operator_pos = self.position;
+            let Some((operator, operator_nodes)) = self.match_operator(operators, false)? else {
+                break;
+            };
+            // Looser operators are left to the caller, and non-associative ones cannot be chained
+            if operator.level < min_level || nonassoc_level == Some(operator.level) {
+                self.position = operator_pos;
+                break;
+            }
+
+            if operator.associativity == Associativity::Postfix {
+                nodes = vec![self.operator_node(node_type.clone(), vec![("operand", nodes), ("operator", operator_nodes)], start_pos)];
+            } else {
+                let right_level = if operator.associativity == Associativity::Right { operator.level } else { operator.level + 1 };
+                let Some((right_nodes, _)) = self.eval_precedence(operand, operators, right_level, node_type.clone())? else {
+                    self.position = operator_pos;
+                    break;
+                };
+                nodes = vec![self.operator_node(node_type.clone(), vec![("left", nodes), ("operator", operator_nodes), ("right", right_nodes)], start_pos)];
+                if operator.associativity == Associativity::NonAssoc {
+                    nonassoc_level = Some(operator.level);
+                }
+            }
+            applied = true;
+        }
+
+        Ok(Some((nodes, applied)))
+    }
+
+    /// Matches the longest of the prefix operators, or of the binary and postfix operators.
+    fn match_operator<'o>(&mut self, operators: &'o [Operator], prefix: bool) -> Result<Option<(&'o Operator, Vec<Node>)>, ParseError> {
+        let start_pos = self.position;
+        let mut longest: Option<(&'o Operator, Vec<Node>, usize)> = None;
+        for operator in operators.iter() {
+            if (operator.associativity == Associativity::Prefix) != prefix {
+                continue;
+            }
+            self.position = start_pos;
+            if let Some(nodes) = self.eval(&operator.expression)? {
+                if longest.as_ref().map_or(true, |(_, _, end)| self.position > *end) {
+                    longest = Some((operator, nodes, self.position));
+                }
+            }
+        }
+        match longest {
+            Some((operator, nodes, end)) => {
+                self.position = end;
+                Ok(Some((operator, nodes)))
+            }
+            None => {
+                self.position = start_pos;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Builds the node of an operator application, labelling the nodes of each part.
+    fn operator_node(&self, node_type: node::NodeType, parts: Vec<(&str, Vec<Node>)>, start_pos: usize) -> Node {
+        let mut children = vec![];
+        for (label, nodes) in parts {
+            for mut node in nodes {
+                if node.label.is_none() {
+                    node.label = Some(label.to_string());
+                }
+                children.push(node);
+            }
+        }
+        Node::new_with_position(node_type, children, None, start_pos, self.position)
+    }
+
+    fn get_longest_expression_match(&mut self, expressions: &[Expression]) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let mut longest_end = start_pos;
+        let mut longest_nodes = None;
+
+        for expr in expressions.iter() {
+            let (result, committed) = self.eval_alternative(&expr)?;
+            let new_end_pos = self.position;
+            self.position = start_pos; // Reset position to start for each expression evaluation
+            if committed {
+                // A committed alternative wins without trying the remaining ones
+                longest_end = new_end_pos;
+                longest_nodes = result;
+                break;
+            }
+            if result.is_some() && (new_end_pos > longest_end || longest_nodes.is_none()) {
+                longest_end = new_end_pos;
+                longest_nodes = result;
+            }
+        }
+        if longest_nodes.is_none() {
+            self.position = start_pos; // Reset position if no matches found
+        } else {
+            self.position = longest_end; // Update position to the end of the longest match
+        }
+        Ok(longest_nodes)
+    }
+
+    fn call_rule(&mut self, rule_name: &str, _protected: bool) -> Result<Option<Vec<Node>>, ParseError> {
+        self.debug_log(&format!("Calling rule: {}", rule_name));
+        self.debug_indent_level += 1;
+        // Unprotected calls come from the seed growing of a rule that is already on the stack
+        if _protected {
+            self.rule_stack.push(rule_name.to_string());
+        }
+        let result = match rule_name {
+            "document" => self._document(),
+            "statement" => self._statement(),
+            "assignment" => self._assignment(),
+            "call" => self._call(),
+            "arguments" => self._arguments(),
+            "address" => self._address(),
+            "color" => self._color(),
+            "pause" => self._pause(),
+            "flag" => self._flag(),
+            "word" => self._word(),
+            "block" => self._block(),
+            "statements" => self._statements(),
+            "value" => self._value(),
+            "number" => self._number(),
+            "string" => self._string(),
+            "identifier" => self._identifier(),
+
+            _ => Err(ParseError::internal(format!("Unknown rule: {}", rule_name))),
+        };
+        if _protected {
+            self.rule_stack.pop();
+        }
+        result
+    }
+
+    fn recovery_expressions(&self, rule_name: &str) -> Option<Vec<Expression>> {
+        match rule_name {
+
+            _ => None,
+        }
+    }
+
+    /// Calls a rule, reporting the rule by its name instead of by what it expects if it fails where
+    /// it starts.
+    fn call_rule_expecting_name(&mut self, rule_name: &str) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        self.__skip_trivia()?;
+        let content_start = self.position;
+        self.position = start_pos;
+        let expected_len = if self.last_position == content_start { self.expected.len() } else { 0 };
+
+        let nodes = self.call_rule(rule_name, true)?;
+        self.debug_indent_level -= 1;
+        if nodes.is_none() && self.last_position == content_start {
+            self.expected.truncate(expected_len);
+            self.position = content_start;
+            self.record_failure(|| rule_name.to_string());
+            self.position = start_pos;
+        }
+        Ok(nodes)
+    }
+
+    /// Calls a rule declared with `@recover`. If the rule fails after matching part of the input,
+    /// it is matched again with one missing literal inserted where it failed. If that fails too,
+    /// the input up to the end of the next match of a synchronisation expression is replaced by an
+    /// error node.
+    fn call_rule_recovering(&mut self, rule_name: &str) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        self.__skip_trivia()?;
+        let content_start = self.position;
+        self.position = start_pos;
+        let diagnostics_len = self.diagnostics.len();
+
+        // Track how far this call gets on its own, keeping the furthest failure of the whole parse
+        let outer_position = self.last_position;
+        let outer_expected = std::mem::take(&mut self.expected);
+        let outer_rule_stack = std::mem::take(&mut self.last_rule_stack);
+        self.last_position = content_start;
+        let nodes = self.call_rule(rule_name, true)?;
+        self.debug_indent_level -= 1;
+        let (failure_pos, failure_expected) = (self.last_position, self.expected.clone());
+        self.restore_failure(outer_position, outer_expected, outer_rule_stack);
+        if nodes.is_some() || failure_pos <= content_start {
+            return Ok(nodes);
+        }
+
+        self.diagnostics.truncate(diagnostics_len);
+        self.position = start_pos;
+        self.missing_at = Some(failure_pos);
+        let nodes = self.call_rule(rule_name, true)?;
+        self.debug_indent_level -= 1;
+        self.missing_at = None;
+        if nodes.is_some() {
+            return Ok(nodes);
+        }
+
+        // Skip to the end of the next synchronisation expression, or to the end of the input
+        self.diagnostics.truncate(diagnostics_len);
+        let (last_position, expected) = (self.last_position, self.expected.clone());
+        let last_rule_stack = self.last_rule_stack.clone();
+        let expressions = self.recovery_expressions(rule_name).unwrap_or_default();
+        self.position = failure_pos;
+        'scan: while self.position < self.content.len() {
+            let scan_pos = self.position;
+            for expression in expressions.iter() {
+                if self.eval(expression)?.is_some() {
+                    break 'scan;
+                }
+                self.position = scan_pos;
+            }
+            self.position += self.content[scan_pos..].chars().next().map_or(1, char::len_utf8);
+        }
+        self.last_position = last_position;
+        self.expected = expected;
+        self.last_rule_stack = last_rule_stack;
+
+        Ok(Some(vec![self.error_node(content_start, failure_pos, &failure_expected)]))
+    }
+
+    /// Returns a missing node for a literal that failed to match where a recovering rule failed,
+    /// so that the rule can be matched again past it.
+    fn missing_node(&mut self, expression: &Expression) -> Result<Option<Node>, ParseError> {
+        let missing_at = match self.missing_at {
+            Some(missing_at) => missing_at,
+            None => return Ok(None),
+        };
+        let (value, text) = match expression {
+            Expression::StringLiteral(string) => (string.to_string(), string.to_string()),
+            Expression::Keyword(keyword) => match self.get_keywords_map().get(*keyword) {
+                Some(text) => (keyword.to_string(), text.clone()),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let start_pos = self.position;
+        self.__skip_trivia()?;
+        let position = self.position;
+        self.position = start_pos;
+        if position != missing_at {
+            return Ok(None);
+        }
+
+        self.missing_at = None;
+        let (line, column, column_utf16) = self.get_line_col(position);
+        self.diagnostics.push(Diagnostic {
+            message: format!("missing '{}' at line {}, column {}", text, line, column),
+            start_position: position,
+            end_position: position,
+            line,
+            column,
+            column_utf16,
+            expected: vec![format!("'{}'", text.escape_debug())],
+            found: self.content[position..].chars().next().map(|c| c.to_string()),
+        });
+        Ok(Some(Node::new_with_position(NodeType::_Missing, vec![], Some(value), position, position)))
+    }
+
+    /// Records a diagnostic for the input from `failure_pos` to the current position, which could
+    /// not be parsed, and returns the node standing in for the input from `start_pos`.
+    fn error_node(&mut self, start_pos: usize, failure_pos: usize, expected: &[std::string::String]) -> Node {
+        let error = self.syntax_error(failure_pos, expected, &[]);
+        self.diagnostics.push(Diagnostic {
+            message: error.message(),
+            start_position: failure_pos,
+            end_position: self.position,
+            line: error.line,
+            column: error.column,
+            column_utf16: error.column_utf16,
+            expected: error.expected,
+            found: error.found,
+        });
+        let skipped = self.content[start_pos..self.position].to_string();
+        Node::new_with_position(NodeType::_Error, vec![], Some(skipped), start_pos, self.position)
+    }
+
+    fn __skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            let start_pos = self.position;
+            self.__ignore_between()?;
+            if self.position == start_pos {
+                return Ok(());
+            }
+        }
+    }
+
+    fn __ignore_between(&mut self) -> Result<(), ParseError> {
+        let regexes: [&'static str; 2] = [
+            r#"^(?:[ \t]+)"#,
+            r#"^(?:#[^\n]*)"#
+        ];
+
+        let start_pos = self.position;
+        let mut longest_end = self.position;
+        for regex in regexes {
+            self.compile_regex(regex)?;
+            self.position = start_pos;
+            if let Some(captures) = self.regexes[regex].captures(&self.content[self.position..]) {
+                let matched_str = captures.get(0).unwrap().as_str();
+                self.position += matched_str.len();
+                if self.position > longest_end {
+                    longest_end = self.position;
+                }
+            }
+        }
+
+        self.position = longest_end;
+
+        Ok(())
+    }
+
+    fn _document(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::and(
+            Expression::and(
+                Expression::and(
+                    Expression::repeat_zero(
+                            Keyword("NEWLINE")
+                        ),
+                    Expression::delimit_repeat_zero(
+                            Rule("statement"),
+                        Expression::repeat_one(
+                                Keyword("NEWLINE")
+                            )
+                        )
+                    ),
+                Expression::repeat_zero(
+                        Keyword("NEWLINE")
+                    )
+                ),
+                Expression::EndOfInput
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Document, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _statement(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 8] = [
+                Rule("assignment"),
+                Rule("call"),
+                Rule("address"),
+                Rule("color"),
+                Rule("pause"),
+                Rule("flag"),
+                Rule("word"),
+                Rule("block"),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Statement, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _assignment(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::and(
+            Expression::and(
+                Expression::and(
+                    Expression::and(
+                            Keyword("KW_LET"),
+                            Expression::Cut
+                        ),
+                    Expression::label(
+                        "name",
+                            Rule("identifier")
+                        )
+                    ),
+                    StringLiteral("=")
+                ),
+            Expression::label(
+                "value",
+                    Rule("value")
+                )
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Assignment, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _call(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::and(
+            Expression::and(
+                Expression::and(
+                    Expression::and(
+                        Expression::label(
+                            "callee",
+                                Rule("identifier")
+                            ),
+                        Expression::lookahead(
+                                StringLiteral("(")
+                            )
+                        ),
+                        StringLiteral("(")
+                    ),
+                Expression::meta(
+                        Rule("arguments")
+                    )
+                ),
+                StringLiteral(")")
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Call, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _arguments(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::delimit_repeat_zero(
+            Expression::label(
+                "args",
+                    Rule("value")
+                ),
+                StringLiteral(",")
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Arguments, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _address(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::delimit_repeat_range(
+            Expression::label(
+                "octets",
+                    RegexLiteral(r#"^(?:[0-9]{1,3})"#)
+                ),
+                StringLiteral("."),
+            4,
+            Some(4)
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Address, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _color(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 2] = [
+            Expression::and(
+                StringLiteral("0x"),
+            Expression::repeat_range(
+                    RegexLiteral(r#"^(?:[0-9a-f])"#),
+                6,
+                Some(6)
+                )
+            ),
+            Expression::and(
+                StringLiteral("0x"),
+            Expression::repeat_range(
+                    RegexLiteral(r#"^(?:[0-9a-f])"#),
+                3,
+                Some(3)
+                )
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Color, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _pause(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::repeat_range(
+                StringLiteral("."),
+            3,
+            None
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Pause, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _flag(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 2] = [
+            Expression::and(
+            Expression::and(
+                Expression::lookahead(
+                    Expression::and(
+                        Expression::and(
+                                StringLiteral("-"),
+                                Expression::Cut
+                            ),
+                            StringLiteral("-")
+                        )
+                    ),
+                    StringLiteral("--")
+                ),
+            Expression::label(
+                "name",
+                    Rule("identifier")
+                )
+            ),
+            Expression::and(
+                StringLiteral("-"),
+            Expression::label(
+                "name",
+                    Rule("identifier")
+                )
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Flag, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _word(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::and(
+            Expression::and(
+                Expression::not_lookahead(
+                        Keyword("KW_LET")
+                    ),
+                    Rule("identifier")
+                ),
+            Expression::discard(
+                Expression::repeat_range(
+                        RegexLiteral(r#"^(?:[!?])"#),
+                    0,
+                    Some(2)
+                    )
+                )
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Word, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _block(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+            Expression::and(
+            Expression::and(
+                    StringLiteral("{"),
+                    Rule("statements")
+                ),
+                StringLiteral("}")
+            ),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Block, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _value(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 3] = [
+                Rule("number"),
+                Rule("string"),
+                Rule("identifier"),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Value, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _number(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+                RegexLiteral(r#"^(?:[0-9]+)"#),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Number, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _string(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+                RegexLiteral(r#"^(?:"[^"\n]*")"#),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::String, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _identifier(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let start_pos = self.position;
+        let expressions: [Expression; 1] = [
+                RegexLiteral(r#"^(?:[a-z_]+)"#),
+        ];
+
+        if let Some(matches) = self.get_longest_expression_match(&expressions)? {
+            let node = Node::new_with_position(node::NodeType::Identifier, matches, None, start_pos, self.position);
+            return Ok(Some(vec![node]));
+        }
+
+        Ok(None)
+    }
+
+    fn _statements(&mut self) -> Result<Option<Vec<Node>>, ParseError> {
+        let expressions: [Expression; 1] = [
+            Expression::delimit_repeat_zero(
+            Expression::label(
+                "items",
+                    Rule("statement")
+                ),
+                StringLiteral(";")
+            ),
+        ];
+
+        self.get_longest_expression_match(&expressions)
+    }
+
+
+}
+
+mod expression {
+    use std::fmt;
+
+    #[derive(Clone, PartialEq, Eq)]
+    #[allow(dead_code)]
+    pub enum Expression {
+        Rule(&'static str),
+        RegexLiteral(&'static str),
+        StringLiteral(&'static str),
+        Keyword(&'static str),
+        EndOfInput,
+        Cut,
+        Or(Box<Expression>, Box<Expression>),
+        And(Box<Expression>, Box<Expression>),
+        DelimitRepeatOne(Box<Expression>, Box<Expression>),
+        DelimitRepeatZero(Box<Expression>, Box<Expression>),
+        Optional(Box<Expression>),
+        RepeatOne(Box<Expression>),
+        RepeatZero(Box<Expression>),
+        Discard(Box<Expression>),
+        Meta(Box<Expression>),
+        RepeatRange(Box<Expression>, usize, Option<usize>),
+        DelimitRepeatRange(Box<Expression>, Box<Expression>, usize, Option<usize>),
+        Lookahead(Box<Expression>),
+        NotLookahead(Box<Expression>),
+        Label(&'static str, Box<Expression>),
+    }
+
+    impl fmt::Debug for Expression {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Expression::Rule(rule) => write!(f, "{}", rule),
+                Expression::RegexLiteral(regex) => write!(f, "{}", regex),
+                Expression::StringLiteral(string) => write!(f, "{}", string),
+                Expression::Keyword(keyword) => write!(f, "{}", keyword),
+                Expression::EndOfInput => write!(f, "ENDMARKER"),
+                Expression::Cut => write!(f, "^"),
+                Expression::Or(left, right) => write!(f, "{:?} | {:?}", left, right),
+                Expression::And(left, right) => write!(f, "{:?} & {:?}", left, right),
+                Expression::DelimitRepeatOne(left, right) => write!(f, "({:?}),({:?})+", left, right),
+                Expression::DelimitRepeatZero(left, right) => write!(f, "({:?}),({:?})*", left, right),
+                Expression::Optional(expr) => write!(f, "({:?})?", expr),
+                Expression::RepeatOne(expr) => write!(f, "({:?})+", expr),
+                Expression::RepeatZero(expr) => write!(f, "({:?})*", expr),
+                Expression::Discard(expr) => write!(f, "~({:?})", expr),
+                Expression::Meta(expr) => write!(f, "$({:?})", expr),
+                Expression::RepeatRange(expr, min, max) => write!(f, "({:?}){{{},{}}}", expr, min, max.map_or(String::new(), |max| max.to_string())),
+                Expression::DelimitRepeatRange(left, right, min, max) => write!(f, "({:?}),({:?}){{{},{}}}", left, right, min, max.map_or(String::new(), |max| max.to_string())),
+                Expression::Lookahead(expr) => write!(f, ">({:?})", expr),
+                Expression::NotLookahead(expr) => write!(f, "!({:?})", expr),
+                Expression::Label(label, expr) => write!(f, "{}=({:?})", label, expr),
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    impl Expression {
+        pub fn or(left: Expression, right: Expression) -> Self {
+            Expression::Or(Box::new(left), Box::new(right))
+        }
+        pub fn and(left: Expression, right: Expression) -> Self {
+            Expression::And(Box::new(left), Box::new(right))
+        }
+        pub fn delimit_repeat_one(left: Expression, right: Expression) -> Self {
+            Expression::DelimitRepeatOne(Box::new(left), Box::new(right))
+        }
+        pub fn delimit_repeat_zero(left: Expression, right: Expression) -> Self {
+            Expression::DelimitRepeatZero(Box::new(left), Box::new(right))
+        }
+        pub fn optional(expr: Expression) -> Self {
+            Expression::Optional(Box::new(expr))
+        }
+        pub fn repeat_one(expr: Expression) -> Self {
+            Expression::RepeatOne(Box::new(expr))
+        }
+        pub fn repeat_zero(expr: Expression) -> Self {
+            Expression::RepeatZero(Box::new(expr))
+        }
+        pub fn repeat_range(expr: Expression, min: usize, max: Option<usize>) -> Self {
+            Expression::RepeatRange(Box::new(expr), min, max)
+        }
+        pub fn delimit_repeat_range(left: Expression, right: Expression, min: usize, max: Option<usize>) -> Self {
+            Expression::DelimitRepeatRange(Box::new(left), Box::new(right), min, max)
+        }
+        pub fn discard(expr: Expression) -> Self {
+            Expression::Discard(Box::new(expr))
+        }
+        pub fn meta(expr: Expression) -> Self {
+            Expression::Meta(Box::new(expr))
+        }
+        pub fn lookahead(expr: Expression) -> Self {
+            Expression::Lookahead(Box::new(expr))
+        }
+        pub fn not_lookahead(expr: Expression) -> Self {
+            Expression::NotLookahead(Box::new(expr))
+        }
+        pub fn label(label: &'static str, expr: Expression) -> Self {
+            Expression::Label(label, Box::new(expr))
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[allow(dead_code)]
+    pub enum Associativity {
+        Left,
+        Right,
+        NonAssoc,
+        Prefix,
+        Postfix,
+    }
+
+    /// An operator of a precedence rule. Operators of higher levels bind more tightly.
+    #[derive(Debug, Clone)]
+    pub struct Operator {
+        pub level: usize,
+        pub associativity: Associativity,
+        pub expression: Expression,
+    }
+
+    #[allow(dead_code)]
+    impl Operator {
+        pub fn new(level: usize, associativity: Associativity, expression: Expression) -> Self {
+            Operator { level, associativity, expression }
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub mod node {
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum NodeType {
+        Document,
+        Statement,
+        Assignment,
+        Call,
+        Arguments,
+        Address,
+        Color,
+        Pause,
+        Flag,
+        Word,
+        Block,
+        Value,
+        Number,
+        String,
+        Identifier,
+        _String,
+        _Discard,
+        _Error,
+        _Missing,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Node {
+        pub node_type: NodeType,
+        pub children: Vec<Node>,
+        pub value: Option<String>,
+        pub label: Option<String>,
+        pub start_position: usize,
+        pub end_position: usize,
+    }
+
+    impl Node {
+        pub fn new(node_type: NodeType, children: Vec<Node>, value: Option<String>) -> Self {
+            Node {
+                node_type,
+                children,
+                value,
+                label: None,
+                start_position: 0,
+                end_position: 0,
+            }
+        }
+
+        pub fn formatted(&self, indent: usize, indent_string: &str) -> String {
+            let mut result = String::new();
+            let indent_str = indent_string.repeat(indent);
+            result.push_str(&indent_str);
+            if let Some(label) = &self.label {
+                result.push_str(&format!("{}=", label));
+            }
+            if self.node_type != NodeType::_String {
+                result.push_str(&format!("{:?}: ", self.node_type));
+            }
+            if let Some(val) = &self.value {
+                result.push_str(&format!("\"{}\"", val.escape_debug()));
+            }
+            for child in &self.children {
+                result.push_str(&format!("\n{}", child.formatted(indent + 1, indent_string)));
+            }
+            result
+        }
+
+        /// Returns the first child with the given label.
+        pub fn field(&self, label: &str) -> Option<&Node> {
+            self.children.iter().find(|child| child.label.as_deref() == Some(label))
+        }
+
+        /// Returns all children with the given label, in order.
+        pub fn fields(&self, label: &str) -> Vec<&Node> {
+            self.children.iter().filter(|child| child.label.as_deref() == Some(label)).collect()
+        }
+
+        pub fn pretty_print(&self) {
+            println!("{}", self.formatted(0, "    "));
+        }
+
+        pub(super) fn extend(&mut self, children: &Vec<Node>) {
+            self.children.extend(children.iter().cloned());
+        }
+
+        pub(super) fn new_with_position(
+            node_type: NodeType,
+            children: Vec<Node>,
+            value: Option<String>,
+            start_position: usize,
+            end_position: usize,
+        ) -> Self {
+            Node {
+                node_type,
+                children,
+                value,
+                label: None,
+                start_position,
+                end_position,
+            }
+        }
+
+        pub(super) fn get_end_pos(&self) -> usize {
+            if let Some(last_child) = self.children.last() {
+                last_child.get_end_pos()
+            } else {
+                self.end_position
+            }
+        }
+
+        pub(super) fn append(&mut self, child: Node) {
+            self.children.push(child);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,114 @@
+//! Checks precedence climbing, with the parser generated from `tests/grammars/calc.grm` and with
+//! the interpreter, which must parse alike.
+
+mod common;
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/calc.rs"]
+mod calc;
+
+use calc::CalcParser;
+use calc::node::Node;
+
+impl_tree!(calc::node::Node);
+
+/// Parses an expression with the generated parser, checking that the interpreter parses it alike,
+/// and returns it with each operation in brackets, such as `((1 + (2 * 3)) - 4)`. Errors are
+/// returned as their first line.
+fn parse(input: &str) -> Result<String, String> {
+    let gramspec = common::load("calc");
+    common::parse_both(&gramspec, input, CalcParser::new().parse(input.to_string()))
+        .map_err(|err| err.lines().next().unwrap_or_default().to_string())?;
+    let file = CalcParser::new().parse(input.to_string()).unwrap();
+    Ok(bracketed(input, &file.children[0]))
+}
+
+/// Writes an operation as its parts in brackets, each operand written the same way. The parts are
+/// the runs of children with the same label, such as the `'(' expr ')'` of an operand.
+fn bracketed(input: &str, node: &Node) -> String {
+    if node.field("operator").is_none() {
+        return input[node.start_position..node.end_position].trim().to_string();
+    }
+    let mut parts: Vec<Vec<&Node>> = Vec::new();
+    for child in &node.children {
+        match parts.last_mut() {
+            Some(part) if part[0].label == child.label => part.push(child),
+            _ => parts.push(vec![child]),
+        }
+    }
+    let parts: Vec<String> = parts
+        .iter()
+        .map(|part| match part.as_slice() {
+            [single] => bracketed(input, single),
+            [first, .., last] => input[first.start_position..last.end_position].trim().to_string(),
+            [] => unreachable!(),
+        })
+        .collect();
+    format!("({})", parts.join(" "))
+}
+
+#[test]
+fn later_levels_bind_tighter() {
+    assert_eq!(parse("1 + 2 * 3 - 4"), Ok("((1 + (2 * 3)) - 4)".to_string()));
+    assert_eq!(parse("1 * 2 + 3 * 4"), Ok("((1 * 2) + (3 * 4))".to_string()));
+    assert_eq!(parse("1 < 2 + 3"), Ok("(1 < (2 + 3))".to_string()));
+    assert_eq!(parse("(1 + 2) * 3"), Ok("((1 + 2) * 3)".to_string()));
+    assert_eq!(parse("1"), Ok("1".to_string()));
+}
+
+#[test]
+fn left_associative_operators_group_to_the_left() {
+    assert_eq!(parse("1 - 2 - 3"), Ok("((1 - 2) - 3)".to_string()));
+    assert_eq!(parse("8 / 4 * 2"), Ok("((8 / 4) * 2)".to_string()));
+}
+
+#[test]
+fn right_associative_operators_group_to_the_right() {
+    assert_eq!(parse("2 ** 3 ** 2"), Ok("(2 ** (3 ** 2))".to_string()));
+}
+
+#[test]
+fn non_associative_operators_do_not_chain() {
+    assert_eq!(parse("1 == 2"), Ok("(1 == 2)".to_string()));
+    assert_eq!(
+        parse("1 < 2 < 3"),
+        Err("expected one of '==', '+', '-', '*', '/', '**', '!' or end of input but found '<' at line 1, column 7".to_string())
+    );
+    assert!(parse("1 == 2 < 3").is_err());
+}
+
+#[test]
+fn prefix_and_postfix_operators_bind_at_their_level() {
+    assert_eq!(parse("--1"), Ok("(- (- 1))".to_string()));
+    assert_eq!(parse("3!!"), Ok("((3 !) !)".to_string()));
+    // The prefix level comes after `**` and before the postfix level
+    assert_eq!(parse("-2 ** 2"), Ok("((- 2) ** 2)".to_string()));
+    assert_eq!(parse("-(2)!"), Ok("(- ((2) !))".to_string()));
+    assert_eq!(parse("-1 + -2"), Ok("((- 1) + (- 2))".to_string()));
+}
+
+#[test]
+fn operations_label_their_parts() {
+    let file = CalcParser::new().parse("1 + -2!".to_string()).unwrap();
+    let sum = &file.children[0];
+    assert_eq!(sum.field("operator").unwrap().value.as_deref(), Some("+"));
+    assert_eq!(sum.field("left").unwrap().start_position, 0);
+    let negation = sum.field("right").unwrap();
+    assert_eq!(negation.field("operator").unwrap().value.as_deref(), Some("-"));
+    let factorial = negation.field("operand").unwrap();
+    assert_eq!(factorial.field("operator").unwrap().value.as_deref(), Some("!"));
+    assert!(factorial.field("left").is_none());
+}
+
+#[test]
+fn missing_operands_are_rejected() {
+    assert_eq!(
+        parse("1 +"),
+        Err("expected one of '-', r'[0-9]+' or '(' but found end of input at line 1, column 4".to_string())
+    );
+    assert_eq!(
+        parse("* 2"),
+        Err("expected one of '-', r'[0-9]+' or '(' but found '*' at line 1, column 1".to_string())
+    );
+}
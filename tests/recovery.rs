@@ -0,0 +1,111 @@
+//! Checks error recovery, with the parser generated from `tests/grammars/statements.grm` and with
+//! the interpreter, which must recover alike. The grammar recovers `statement` at the next `;` or
+//! before the next `}`.
+
+mod common;
+
+#[allow(dead_code, unused_imports, clippy::all)]
+#[rustfmt::skip]
+#[path = "parsers/statements.rs"]
+mod statements;
+
+use statements::node::{Node, NodeType};
+use statements::{Diagnostic, StatementsParser};
+
+impl_tree!(statements::node::Node);
+
+/// Parses the input with recovery with the generated parser, checking that the interpreter
+/// recovers alike.
+fn recover(input: &str) -> (Node, Vec<Diagnostic>) {
+    let gramspec = common::load("statements");
+    common::recover_both(&gramspec, input, StatementsParser::new().parse_with_recovery(input.to_string())).unwrap();
+    StatementsParser::new().parse_with_recovery(input.to_string()).unwrap()
+}
+
+/// Lists the error and missing nodes of a tree, with their text and span.
+fn problems(node: &Node) -> Vec<String> {
+    match node.node_type {
+        NodeType::_Error | NodeType::_Missing => vec![format!(
+            "{:?} {:?} @{}..{}",
+            node.node_type,
+            node.value.as_deref().unwrap_or_default(),
+            node.start_position,
+            node.end_position
+        )],
+        _ => node.children.iter().flat_map(problems).collect(),
+    }
+}
+
+/// Lists the diagnostics with their span.
+fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("{} @{}..{}", diagnostic.message, diagnostic.start_position, diagnostic.end_position))
+        .collect()
+}
+
+#[test]
+fn input_without_errors_is_parsed_as_is() {
+    let input = "let a = 1; print a; { print 2; }";
+    let (tree, diagnostics) = recover(input);
+    assert!(diagnostics.is_empty());
+    assert_eq!(common::render(&tree), common::render(&StatementsParser::new().parse(input.to_string()).unwrap()));
+}
+
+#[test]
+fn missing_literals_become_missing_nodes() {
+    let (tree, diagnostics) = recover("let a 1;");
+    assert_eq!(problems(&tree), ["_Missing \"=\" @6..6"]);
+    assert_eq!(messages(&diagnostics), ["missing '=' at line 1, column 7 @6..6"]);
+
+    // The statement after the one missing its ';' is parsed as usual
+    let (tree, diagnostics) = recover("print 1 print 2;");
+    assert_eq!(problems(&tree), ["_Missing \";\" @8..8"]);
+    assert_eq!(messages(&diagnostics), ["missing ';' at line 1, column 9 @8..8"]);
+    assert_eq!(tree.children.len(), 2);
+}
+
+#[test]
+fn unparsable_statements_become_error_nodes() {
+    // The error node covers the statement up to its ';'
+    let (tree, diagnostics) = recover("let a = 1; print ; let b = 2;");
+    let types: Vec<&NodeType> = tree.children.iter().map(|child| &child.node_type).collect();
+    assert_eq!(types, [&NodeType::Statement, &NodeType::_Error, &NodeType::Statement]);
+    assert_eq!(problems(&tree), ["_Error \"print ;\" @11..18"]);
+    assert_eq!(messages(&diagnostics), ["expected r'[0-9]+' or r'[a-z]+' but found ';' at line 1, column 18 @17..18"]);
+
+    // Inside a block, the error node stops before the '}' that closes it
+    let (tree, diagnostics) = recover("{ let = 1 } print 2;");
+    assert_eq!(problems(&tree), ["_Error \"let = 1\" @2..9"]);
+    assert_eq!(messages(&diagnostics), ["expected r'[a-z]+' but found '=' at line 1, column 7 @6..9"]);
+    assert_eq!(tree.children.len(), 2);
+}
+
+#[test]
+fn every_error_is_reported() {
+    let (tree, diagnostics) = recover("let a = 1;\nprint ;\nlet = 2;\nprint a");
+    assert_eq!(problems(&tree), ["_Error \"print ;\" @11..18", "_Error \"let = 2;\" @19..27", "_Missing \";\" @35..35"]);
+    assert_eq!(
+        messages(&diagnostics),
+        [
+            "expected r'[0-9]+' or r'[a-z]+' but found ';' at line 2, column 7 @17..18",
+            "expected r'[a-z]+' but found '=' at line 3, column 5 @23..27",
+            "missing ';' at line 4, column 8 @35..35",
+        ]
+    );
+    assert_eq!((diagnostics[2].line, diagnostics[2].column, diagnostics[2].column_utf16), (4, 8, 8));
+}
+
+#[test]
+fn unrecoverable_errors_are_reported_alike() {
+    let gramspec = common::load("statements");
+    let expected = "expected one of 'let', 'print', '{' or end of input but found '}'";
+    for (input, position) in [
+        ("}", "line 1, column 1\n1 | }\n  | ^"),
+        ("print ;\n}", "line 2, column 1\n2 | }\n  | ^"),
+        ("let a = 1; }", "line 1, column 12\n1 | let a = 1; }\n  |            ^"),
+    ] {
+        let result = common::recover_both(&gramspec, input, StatementsParser::new().parse_with_recovery(input.to_string()));
+        assert_eq!(result, Err(format!("{} at {}", expected, position)), "{:?}", input);
+    }
+}
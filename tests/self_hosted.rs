@@ -0,0 +1,65 @@
+//! Checks the parser of the grammar language, which is generated from `grammars/gramspec.grm`
+//! and parses grammar files when a `Parser` is made `self_hosted`.
+//!
+//! After changing the grammar or the generator, regenerate the parser with
+//! `gramspec-parser-generator generate grammars/gramspec.grm -o
+//! src/gramspec_parser/parser/self_hosted/gramspec_parser.rs`. The grammar is read by the
+//! hand-written parser, so a grammar the current version of the generated parser cannot read yet
+//! can still be bootstrapped. `generated_parser_reads_its_own_grammar` then checks that the new
+//! parser reads it as the hand-written parser does.
+
+use std::collections::BTreeSet;
+use std::fs;
+
+use gramspec::{GramSpec, Parser};
+
+/// The grammar of the grammar language.
+const GRAMMAR_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/grammars/gramspec.grm");
+/// The checked-in parser generated from `GRAMMAR_FILE`.
+const PARSER_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/gramspec_parser/parser/self_hosted/gramspec_parser.rs");
+
+/// Describes every rule and option of a grammar specification, one per line, independently of
+/// where they are defined.
+fn describe(gramspec: &GramSpec) -> BTreeSet<String> {
+    let mut lines = BTreeSet::new();
+    lines.insert(format!("@entry_rule: {}", gramspec.config.entry_rule));
+    lines.insert(format!("@ignore_between: {:?}", gramspec.config.ignore_between));
+    for (name, alternatives) in &gramspec.rules {
+        lines.insert(format!("{}: {:?}", name, alternatives));
+    }
+    for (name, alternatives) in &gramspec.meta_rules {
+        lines.insert(format!("${}: {:?}", name, alternatives));
+    }
+    for (name, alternatives) in &gramspec.discard_rules {
+        lines.insert(format!("~{}: {:?}", name, alternatives));
+    }
+    for (name, levels) in &gramspec.precedence {
+        lines.insert(format!("@precedence {}: {:?}", name, levels));
+    }
+    for (name, alternatives) in &gramspec.recovery {
+        lines.insert(format!("@recover {}: {:?}", name, alternatives));
+    }
+    for (name, value) in &gramspec.keywords {
+        lines.insert(format!("{}: {:?}", name, value));
+    }
+    lines
+}
+
+#[test]
+fn generated_parser_is_up_to_date() {
+    let gramspec = gramspec::load_grammar_file(GRAMMAR_FILE).unwrap();
+    let code = gramspec::generate_to_string(&gramspec, gramspec::DEFAULT_PARSER_NAME, gramspec::DEFAULT_INDENT).unwrap();
+    assert!(
+        fs::read_to_string(PARSER_FILE).unwrap() == code,
+        "{} is not the parser generated from {}; regenerate it",
+        PARSER_FILE,
+        GRAMMAR_FILE
+    );
+}
+
+#[test]
+fn generated_parser_reads_its_own_grammar() {
+    let hand_written = gramspec::load_grammar_file(GRAMMAR_FILE).unwrap();
+    let self_hosted = Parser::from_file(GRAMMAR_FILE).and_then(|parser| parser.self_hosted().parse()).unwrap();
+    assert_eq!(describe(&self_hosted), describe(&hand_written));
+}